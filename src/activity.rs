@@ -0,0 +1,97 @@
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local};
+use iced::futures::stream::{self, BoxStream, StreamExt};
+use iced::Subscription;
+use iced_native::subscription::{EventStream, Recipe};
+use iced_native::Hasher;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// What an event is about, the log can be filtered by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Connection,
+    Match,
+    Blacklist,
+    Error,
+    Info,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Kind::Connection,
+        Kind::Match,
+        Kind::Blacklist,
+        Kind::Error,
+        Kind::Info,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Connection => "Connection",
+            Kind::Match => "Matches",
+            Kind::Blacklist => "Blacklist",
+            Kind::Error => "Errors",
+            Kind::Info => "Info",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time: DateTime<Local>,
+    pub kind: Kind,
+    pub text: String,
+}
+
+impl Event {
+    pub fn new(kind: Kind, text: impl Into<String>) -> Self {
+        Self {
+            time: Local::now(),
+            kind,
+            text: text.into(),
+        }
+    }
+}
+
+/// Where the bot sends its events, the GUI receives them through `subscription`.
+#[derive(Debug, Clone)]
+pub struct Sender(UnboundedSender<Event>);
+
+impl Sender {
+    pub fn send(&self, kind: Kind, text: impl Into<String>) {
+        // The GUI is gone when this fails, so there is nobody to tell
+        let _ = self.0.send(Event::new(kind, text));
+    }
+}
+
+/// The receiving end, it is taken by the subscription the first time it runs.
+pub type Receiver = Arc<Mutex<Option<UnboundedReceiver<Event>>>>;
+
+pub fn channel() -> (Sender, Receiver) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (Sender(sender), Arc::new(Mutex::new(Some(receiver))))
+}
+
+/// Listen to the events sent through the `Sender` of `receiver`.
+pub fn subscription(receiver: Receiver) -> Subscription<Event> {
+    Subscription::from_recipe(Events(receiver))
+}
+
+struct Events(Receiver);
+
+impl Recipe<Hasher, iced_native::Event> for Events {
+    type Output = Event;
+
+    fn hash(&self, state: &mut Hasher) {
+        std::any::TypeId::of::<Self>().hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: EventStream) -> BoxStream<'static, Event> {
+        match self.0.lock().unwrap().take() {
+            Some(receiver) => receiver.boxed(),
+            None => stream::empty().boxed(),
+        }
+    }
+}
@@ -1,13 +1,23 @@
-use super::style::Theme;
+use std::path::{Path, PathBuf};
+
+use super::blacklist::{Action, BlackWord, Scope};
+use super::entry::select_file;
+use super::library;
+use super::match_mode::MatchMode;
+use super::player;
+use super::style::{Theme, ERROR_COLOR};
 
 use iced::{button, text_input, Align, Button, Command, Element, Length, Row, Text, TextInput};
 
 #[derive(Debug, Clone)]
 pub struct BlackWordEntry {
-    style: Theme,
-    index: usize,
+    /// The rowid of the entry in `blacklist`, it stays the same when saving.
+    pub id: i64,
 
-    pub word: String,
+    pub word: BlackWord,
+    error: Option<String>,
+    /// Done was pressed and the sound file is being checked, nothing was changed since.
+    checking: bool,
 
     state: BlackWordState,
 }
@@ -19,6 +29,11 @@ enum BlackWordState {
     },
     Editing {
         word_in: text_input::State,
+        mode_btn: button::State,
+        scope_btn: button::State,
+        scope_id_in: text_input::State,
+        action_btn: button::State,
+        path_btn: button::State,
         done_btn: button::State,
         delete_btn: button::State,
     },
@@ -28,6 +43,11 @@ impl Default for BlackWordState {
     fn default() -> Self {
         Self::Editing {
             word_in: text_input::State::new(),
+            mode_btn: button::State::new(),
+            scope_btn: button::State::new(),
+            scope_id_in: text_input::State::new(),
+            action_btn: button::State::new(),
+            path_btn: button::State::new(),
             done_btn: button::State::new(),
             delete_btn: button::State::new(),
         }
@@ -37,26 +57,37 @@ impl Default for BlackWordState {
 #[derive(Debug, Clone)]
 pub enum BlackWordMessage {
     WordChanged(String),
+    NextMode,
+    NextScope,
+    ScopeIdChanged(String),
+    NextAction,
+    ChooseFile,
+    ChoseFile(String),
+    FileAdded(library::Added),
     Edit,
     DoneEditing,
     Delete,
+    /// The result of checking the file at the path, after Done.
+    FileChecked(String, Result<(), String>),
 }
 
 impl BlackWordEntry {
-    pub fn new(index: usize) -> Self {
+    pub fn new(id: i64) -> Self {
         Self {
-            style: Theme::Dark,
-            index,
-            word: String::new(),
+            id,
+            word: BlackWord::default(),
+            error: None,
+            checking: false,
             state: BlackWordState::default(),
         }
     }
 
-    pub fn new_idle(index: usize) -> Self {
+    pub fn new_idle(id: i64) -> Self {
         Self {
-            style: Theme::Dark,
-            index,
-            word: String::new(),
+            id,
+            word: BlackWord::default(),
+            error: None,
+            checking: false,
             state: BlackWordState::Idle {
                 edit_btn: button::State::new(),
             },
@@ -64,66 +95,273 @@ impl BlackWordEntry {
     }
 
     pub fn update(&mut self, message: BlackWordMessage) -> Command<BlackWordMessage> {
+        // A change after Done has to be checked again
+        if !matches!(
+            message,
+            BlackWordMessage::FileChecked(..) | BlackWordMessage::Delete
+        ) {
+            self.checking = false;
+        }
+
         match message {
-            // This is taken care of in gui.rs
-            BlackWordMessage::Delete => {}
-            BlackWordMessage::WordChanged(new) => self.word = new.to_lowercase(),
+            // These are taken care of in gui.rs
+            BlackWordMessage::Delete | BlackWordMessage::DoneEditing => {}
+            BlackWordMessage::WordChanged(new) => {
+                // Lowercasing a regex would change its meaning, e.g. `\W` to `\w`
+                self.word.word = match self.word.mode {
+                    MatchMode::Regex => new,
+                    _ => new.to_lowercase(),
+                }
+            }
+            BlackWordMessage::NextMode => {
+                self.word.mode = self.word.mode.next();
+                if self.word.mode != MatchMode::Regex {
+                    self.word.word = self.word.word.to_lowercase();
+                }
+            }
+            BlackWordMessage::NextScope => self.word.scope = self.word.scope.next(),
+            BlackWordMessage::ScopeIdChanged(new) => self.word.scope_id = new.to_lowercase(),
+            BlackWordMessage::NextAction => self.word.action = self.word.action.next(),
+            BlackWordMessage::ChooseFile => {
+                return Command::perform(select_file(), BlackWordMessage::ChoseFile)
+            }
+            BlackWordMessage::ChoseFile(path) => {
+                if path != "-1" {
+                    return Command::perform(
+                        library::add(PathBuf::from(path)),
+                        BlackWordMessage::FileAdded,
+                    );
+                }
+            }
+            BlackWordMessage::FileAdded(Ok((path, _))) => {
+                self.word.sound_path = path;
+                self.error = None;
+            }
+            BlackWordMessage::FileAdded(Err(e)) => self.error = Some(e),
             BlackWordMessage::Edit => self.state = BlackWordState::default(),
-            BlackWordMessage::DoneEditing => {
-                if !self.word.is_empty() {
-                    self.state = BlackWordState::Idle {
-                        edit_btn: button::State::new(),
-                    }
+            BlackWordMessage::FileChecked(path, checked) => {
+                if self.checking && path == self.word.sound_path {
+                    self.checking = false;
+                    self.error = checked.err();
+                    self.stop_editing_if_valid();
                 }
             }
         }
         Command::none()
     }
 
-    pub fn view(&mut self) -> Element<BlackWordMessage> {
+    /// Determine if Done found something wrong that wasn't fixed yet.
+    pub fn has_errors(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Validate the word and stop editing it when it is valid,
+    /// `duplicate` is whether another entry has the same word and scope.
+    /// The sound of `Action::PlaySound` is checked by the returned command,
+    /// the entry is done after that.
+    pub fn done_editing(&mut self, duplicate: bool) -> Command<BlackWordMessage> {
+        self.error = match self.word.validate() {
+            Err(e) => Some(e),
+            Ok(()) if duplicate => {
+                Some("There is already an entry for this word and scope".to_string())
+            }
+            Ok(()) => None,
+        };
+
+        if self.error.is_none() && self.word.action == Action::PlaySound {
+            self.checking = true;
+            let path = self.word.sound_path.clone();
+            return Command::perform(player::check(path.clone()), move |checked| {
+                BlackWordMessage::FileChecked(path.clone(), checked)
+            });
+        }
+        self.stop_editing_if_valid();
+        Command::none()
+    }
+
+    fn stop_editing_if_valid(&mut self) {
+        if self.error.is_none() {
+            self.state = BlackWordState::Idle {
+                edit_btn: button::State::new(),
+            }
+        }
+    }
+
+    pub fn view(&mut self, style: Theme) -> Element<BlackWordMessage> {
         match &mut self.state {
             BlackWordState::Idle { edit_btn } => {
-                let word_lbl = Text::new(&self.word);
+                let word_lbl = Text::new(&self.word.word);
+                let mode_lbl = Text::new(self.word.mode.label());
+                let scope_lbl = Text::new(self.word.scope_label());
+                let action_lbl = Text::new(self.word.action.label());
                 let edit_btn = Button::new(edit_btn, Text::new("edit"))
                     .on_press(BlackWordMessage::Edit)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
 
                 Row::new()
                     .spacing(20)
                     .push(word_lbl)
+                    .push(mode_lbl)
+                    .push(scope_lbl)
+                    .push(action_lbl)
                     .push(edit_btn)
                     .align_items(Align::Center)
                     .into()
             }
             BlackWordState::Editing {
                 word_in,
+                mode_btn,
+                scope_btn,
+                scope_id_in,
+                action_btn,
+                path_btn,
                 done_btn,
                 delete_btn,
             } => {
-                let word =
-                    TextInput::new(word_in, "Word", &self.word, BlackWordMessage::WordChanged)
-                        .padding(20)
-                        .width(Length::Fill)
-                        .style(self.style);
+                let word = TextInput::new(
+                    word_in,
+                    "Word",
+                    &self.word.word,
+                    BlackWordMessage::WordChanged,
+                )
+                .padding(20)
+                .width(Length::Fill)
+                .style(style);
+
+                let mode = Button::new(mode_btn, Text::new(self.word.mode.label()))
+                    .on_press(BlackWordMessage::NextMode)
+                    .padding(10)
+                    .style(style);
+
+                let scope = Button::new(scope_btn, Text::new(self.word.scope.as_str()))
+                    .on_press(BlackWordMessage::NextScope)
+                    .padding(10)
+                    .style(style);
+
+                let action = Button::new(action_btn, Text::new(self.word.action.label()))
+                    .on_press(BlackWordMessage::NextAction)
+                    .padding(10)
+                    .style(style);
 
                 let done = Button::new(done_btn, Text::new("Done"))
                     .on_press(BlackWordMessage::DoneEditing)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
                 let delete = Button::new(delete_btn, Text::new("Delete"))
                     .on_press(BlackWordMessage::Delete)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
 
-                Row::new()
-                    .spacing(20)
-                    .push(word)
-                    .push(done)
-                    .push(delete)
-                    .align_items(Align::Center)
-                    .into()
+                let mut row = Row::new().spacing(20).push(word).push(mode).push(scope);
+
+                if self.word.scope != Scope::Global {
+                    let placeholder = match self.word.scope {
+                        Scope::Guild => "Guild Id",
+                        Scope::Channel => "Channel Id",
+                        _ => "Trigger word",
+                    };
+                    let scope_id = TextInput::new(
+                        scope_id_in,
+                        placeholder,
+                        &self.word.scope_id,
+                        BlackWordMessage::ScopeIdChanged,
+                    )
+                    .padding(20)
+                    .width(Length::Fill)
+                    .style(style);
+
+                    row = row.push(scope_id);
+                }
+
+                row = row.push(action);
+
+                if self.word.action == Action::PlaySound {
+                    let sound_path = &self.word.sound_path;
+                    let file_name = Path::new(sound_path)
+                        .file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_else(|| sound_path.clone());
+                    let path = Button::new(path_btn, Text::new(file_name))
+                        .on_press(BlackWordMessage::ChooseFile)
+                        .padding(10)
+                        .style(style);
+
+                    row = row.push(path);
+                }
+
+                row = row.push(done).push(delete);
+
+                if let Some(error) = &self.error {
+                    row = row.push(Text::new(error).color(ERROR_COLOR));
+                }
+
+                row.align_items(Align::Center).into()
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_sound_entry() -> BlackWordEntry {
+        let mut entry = BlackWordEntry::new(1);
+        entry.word.word = String::from("spoiler");
+        entry.word.action = Action::PlaySound;
+        entry.word.sound_path = String::from("blocked.mp3");
+        entry
+    }
+
+    fn is_idle(entry: &BlackWordEntry) -> bool {
+        matches!(entry.state, BlackWordState::Idle { .. })
+    }
+
+    fn checked(result: Result<(), String>) -> BlackWordMessage {
+        BlackWordMessage::FileChecked(String::from("blocked.mp3"), result)
+    }
+
+    #[test]
+    fn done_waits_for_the_sound_check() {
+        let mut entry = play_sound_entry();
+        let _ = entry.done_editing(false);
+        assert!(!is_idle(&entry));
+
+        let _ = entry.update(checked(Ok(())));
+        assert!(is_idle(&entry));
+        assert!(!entry.has_errors());
+    }
+
+    #[test]
+    fn bad_sound_stays_in_editing() {
+        let mut entry = play_sound_entry();
+        let _ = entry.done_editing(false);
+        let _ = entry.update(checked(Err(String::from("bad"))));
+        assert!(!is_idle(&entry));
+        assert_eq!(entry.error, Some(String::from("bad")));
+    }
+
+    #[test]
+    fn check_is_ignored_after_a_change() {
+        let mut entry = play_sound_entry();
+        let _ = entry.done_editing(false);
+        let _ = entry.update(BlackWordMessage::WordChanged(String::from("leak")));
+
+        let _ = entry.update(checked(Ok(())));
+        assert!(!is_idle(&entry));
+    }
+
+    #[test]
+    fn no_sound_is_not_checked() {
+        let mut entry = play_sound_entry();
+        entry.word.sound_path = String::from("Path");
+        let _ = entry.done_editing(false);
+        assert_eq!(entry.error, Some(String::from("Choose the sound to play")));
+
+        let mut entry = play_sound_entry();
+        entry.word.action = Action::SuppressAll;
+        let _ = entry.done_editing(false);
+        assert!(is_idle(&entry));
+    }
+}
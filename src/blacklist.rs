@@ -0,0 +1,232 @@
+use super::match_mode::{MatchMode, Pattern, Span};
+use super::word::Word;
+
+/// Where a blacklisted word is enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    Guild,
+    Channel,
+    /// Only when the trigger whose keyword is `scope_id` matched.
+    Trigger,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Global, Scope::Guild, Scope::Channel, Scope::Trigger];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Global => "global",
+            Scope::Guild => "guild",
+            Scope::Channel => "channel",
+            Scope::Trigger => "trigger",
+        }
+    }
+
+    /// The scope after this one in `ALL`, wrapping around.
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|s| *s == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+impl From<&str> for Scope {
+    fn from(s: &str) -> Self {
+        match s {
+            "guild" => Scope::Guild,
+            "channel" => Scope::Channel,
+            "trigger" => Scope::Trigger,
+            _ => Scope::Global,
+        }
+    }
+}
+
+/// What happens when a blacklisted word is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Don't play anything for the message.
+    SuppressAll,
+    /// Don't play the triggers the word applies to, play the rest.
+    SuppressTrigger,
+    /// Play `sound_path` instead of the matched triggers.
+    PlaySound,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [
+        Action::SuppressAll,
+        Action::SuppressTrigger,
+        Action::PlaySound,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::SuppressAll => "suppress_all",
+            Action::SuppressTrigger => "suppress_trigger",
+            Action::PlaySound => "play_sound",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|a| *a == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::SuppressAll => "Block all",
+            Action::SuppressTrigger => "Block trigger",
+            Action::PlaySound => "Play sound",
+        }
+    }
+}
+
+impl From<&str> for Action {
+    fn from(s: &str) -> Self {
+        match s {
+            "suppress_trigger" => Action::SuppressTrigger,
+            "play_sound" => Action::PlaySound,
+            _ => Action::SuppressAll,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlackWord {
+    pub word: String,
+    pub mode: MatchMode,
+    pub scope: Scope,
+    /// Guild id, channel id or trigger keyword, depending on `scope`.
+    pub scope_id: String,
+    pub action: Action,
+    /// The "blocked" sound, only used with `Action::PlaySound`.
+    pub sound_path: String,
+}
+
+impl Default for BlackWord {
+    fn default() -> Self {
+        Self {
+            word: String::new(),
+            mode: MatchMode::Substring,
+            scope: Scope::Global,
+            scope_id: String::new(),
+            action: Action::SuppressAll,
+            sound_path: String::from("Path"),
+        }
+    }
+}
+
+impl BlackWord {
+    /// Check the word can be saved, returning what is wrong with it otherwise.
+    /// The sound file isn't opened here, that is slow, see `player::check`.
+    pub fn validate(&self) -> Result<(), String> {
+        self.mode.validate(&self.word)?;
+
+        match self.scope {
+            Scope::Global => {}
+            Scope::Guild | Scope::Channel => {
+                if self.scope_id.is_empty() || !self.scope_id.chars().all(char::is_numeric) {
+                    return Err("The scope id must be numeric".to_string());
+                }
+            }
+            Scope::Trigger => {
+                if self.scope_id.is_empty() {
+                    return Err("The trigger word can't be empty".to_string());
+                }
+            }
+        }
+
+        if self.action == Action::PlaySound && self.sound_path == "Path" {
+            return Err("Choose the sound to play".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// The word compiled for its mode, to look for it in messages.
+    pub fn pattern(&self) -> Pattern {
+        Pattern::new(self.mode, &self.word)
+    }
+
+    /// Determine if the word is enforced for a message in the given guild and channel
+    /// that matched the `matched` triggers.
+    pub fn in_scope(&self, guild_id: Option<u64>, chn_id: &str, matched: &[(&Word, Span)]) -> bool {
+        match self.scope {
+            Scope::Global => true,
+            Scope::Guild => guild_id.map(|g| g.to_string()).as_ref() == Some(&self.scope_id),
+            Scope::Channel => chn_id == self.scope_id,
+            Scope::Trigger => matched.iter().any(|(w, _)| w.word == self.scope_id),
+        }
+    }
+
+    /// Determine if `Action::SuppressTrigger` should hold back `trigger`, which matched
+    /// at `trigger_span` of the message, when the word was found at `span`.
+    /// With a trigger scope that is the scoped trigger, otherwise it is every trigger
+    /// whose match overlaps the word (e.g. "ass" in "class").
+    pub fn blocks(&self, trigger: &Word, trigger_span: Span, (start, end): Span) -> bool {
+        match self.scope {
+            Scope::Trigger => trigger.word == self.scope_id,
+            _ => {
+                let (trigger_start, trigger_end) = trigger_span;
+                trigger_start < end && start < trigger_end
+            }
+        }
+    }
+
+    /// Short description of the scope for the idle view.
+    pub fn scope_label(&self) -> String {
+        match self.scope {
+            Scope::Global => "Everywhere".to_string(),
+            Scope::Guild => format!("Guild {}", self.scope_id),
+            Scope::Channel => format!("Channel {}", self.scope_id),
+            Scope::Trigger => format!("Trigger \"{}\"", self.scope_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suppress(word: &str, scope: Scope, scope_id: &str) -> BlackWord {
+        BlackWord {
+            word: word.to_string(),
+            scope,
+            scope_id: scope_id.to_string(),
+            action: Action::SuppressTrigger,
+            ..BlackWord::default()
+        }
+    }
+
+    fn trigger(word: &str) -> Word {
+        Word {
+            word: word.to_string(),
+            ..Word::default()
+        }
+    }
+
+    #[test]
+    fn blocks_overlapping_matches() {
+        let black_word = suppress("class", Scope::Global, "");
+        // "first class ass", "class" is at 6..11
+        assert!(black_word.blocks(&trigger("ass"), (8, 11), (6, 11)));
+        assert!(!black_word.blocks(&trigger("ass"), (12, 15), (6, 11)));
+        assert!(!black_word.blocks(&trigger("first"), (0, 5), (6, 11)));
+    }
+
+    #[test]
+    fn blocks_conditions_where_they_matched() {
+        let black_word = suppress("class", Scope::Global, "");
+        // A condition named "greeting" that matched at "hi" in "class hi"
+        assert!(!black_word.blocks(&trigger("greeting"), (6, 8), (0, 5)));
+        // and at "ass" in "class"
+        assert!(black_word.blocks(&trigger("greeting"), (2, 5), (0, 5)));
+    }
+
+    #[test]
+    fn trigger_scope_blocks_only_that_trigger() {
+        let black_word = suppress("class", Scope::Trigger, "horn");
+        assert!(black_word.blocks(&trigger("horn"), (20, 24), (0, 5)));
+        assert!(!black_word.blocks(&trigger("ass"), (2, 5), (0, 5)));
+    }
+}
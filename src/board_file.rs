@@ -0,0 +1,678 @@
+//! Boards saved to a file, to share them between machines or keep them in git.
+//!
+//! Files ending in `.json` are JSON, anything else is TOML. Both hold the same fields:
+//!
+//! ```toml
+//! version = 1
+//! token = "..."            # only when exported with the token
+//!
+//! [settings]
+//! quiet_hours = "22:00-08:00"
+//! timezone = "Europe/Athens"
+//! history_limit = 10000
+//! theme = "dark"           # dark, light or high_contrast
+//! default_chn_id = ""
+//!
+//! [[triggers]]
+//! word = "horn"
+//! mode = "substring"       # substring, whole_word or regex
+//! chn_id = "123456789012345678"
+//! path = "horn.mp3"        # relative to the sound library
+//! hash = "af63bd4c8601b7df"
+//! schedule = ""
+//! probability = 100
+//! priority = 0
+//! condition = ""
+//! source = "file"          # file or tts
+//! tts_template = "{message}"
+//!
+//! [[blacklist]]
+//! word = "spoiler"
+//! mode = "substring"       # substring, whole_word or regex
+//! scope = "global"         # global, guild, channel or trigger
+//! scope_id = ""
+//! action = "suppress_all"  # suppress_all, suppress_trigger or play_sound
+//! sound_path = ""
+//!
+//! [[policies]]
+//! chn_id = "123456789012345678"
+//! policy = "all"           # all, highest_priority, first_in_message or longest
+//! ```
+//!
+//! Fields that are left out get their default, so older files keep loading.
+
+use std::fs;
+
+use super::blacklist::{Action, BlackWord, Scope};
+use super::match_mode::MatchMode;
+use super::policy::{ChannelPolicy, Policy};
+use super::settings::BoardSettings;
+use super::style::Theme;
+use super::undo::Snapshot;
+use super::word::{Source, Word};
+
+use serde::{Deserialize, Serialize};
+use tokio::task;
+
+/// The version written to new files.
+const VERSION: i32 = 1;
+
+/// Everything that is exported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    pub settings: BoardSettings,
+    /// Only exported when asked for.
+    pub token: Option<String>,
+    pub words: Vec<Word>,
+    pub blacklist: Vec<BlackWord>,
+    pub policies: Vec<ChannelPolicy>,
+}
+
+/// What happens to the current board on import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Everything is replaced by the file.
+    Replace,
+    /// The file is added, keeping entries that conflict with it.
+    MergeKeep,
+    /// The file is added, overwriting entries that conflict with it.
+    MergeOverwrite,
+}
+
+impl ImportMode {
+    pub fn next(self) -> Self {
+        match self {
+            ImportMode::Replace => ImportMode::MergeKeep,
+            ImportMode::MergeKeep => ImportMode::MergeOverwrite,
+            ImportMode::MergeOverwrite => ImportMode::Replace,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ImportMode::Replace => "Replace the board",
+            ImportMode::MergeKeep => "Merge, keep existing",
+            ImportMode::MergeOverwrite => "Merge, overwrite existing",
+        }
+    }
+}
+
+impl Board {
+    /// Put the board's entries in `snapshot` according to `mode`, using `new_id` for added ones.
+    /// Triggers conflict when they have the same channel and word, blacklisted words
+    /// when they have the same word and scope and policies when they have the same channel.
+    /// Returns how many entries conflicted.
+    pub fn apply(
+        self,
+        snapshot: &mut Snapshot,
+        mode: ImportMode,
+        mut new_id: impl FnMut() -> i64,
+    ) -> usize {
+        if mode == ImportMode::Replace {
+            *snapshot = Snapshot::default();
+        }
+        let overwrite = mode != ImportMode::MergeKeep;
+
+        merge(
+            &mut snapshot.words,
+            self.words,
+            overwrite,
+            &mut new_id,
+            |a, b| a.chn_id == b.chn_id && a.word == b.word,
+        ) + merge(
+            &mut snapshot.blacklist,
+            self.blacklist,
+            overwrite,
+            &mut new_id,
+            |a, b| a.word == b.word && a.scope == b.scope && a.scope_id == b.scope_id,
+        ) + merge(
+            &mut snapshot.policies,
+            self.policies,
+            overwrite,
+            &mut new_id,
+            |a, b| a.chn_id == b.chn_id,
+        )
+    }
+}
+
+fn merge<T>(
+    current: &mut Vec<(i64, T)>,
+    imported: Vec<T>,
+    overwrite: bool,
+    new_id: &mut impl FnMut() -> i64,
+    same: impl Fn(&T, &T) -> bool,
+) -> usize {
+    let mut conflicts = 0;
+    for item in imported {
+        match current.iter_mut().find(|(_, old)| same(old, &item)) {
+            Some((_, old)) => {
+                conflicts += 1;
+                if overwrite {
+                    *old = item;
+                }
+            }
+            None => current.push((new_id(), item)),
+        }
+    }
+    conflicts
+}
+
+/// Ask where to save `board` and write it there.
+/// Returns the path, or `None` when the user cancelled.
+pub async fn export(board: Board) -> Result<Option<String>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_save_dialog(Some("toml,json"), None).expect("Error opening nfd");
+        let path = match res {
+            nfd::Response::Okay(path) => path,
+            _ => return Ok(None),
+        };
+
+        let text = to_text(board, is_json(&path))
+            .map_err(|e| format!("Failed to export the board: {}", e))?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        Ok(Some(path))
+    })
+}
+
+/// Ask for a board file and read it.
+/// Returns `None` when the user cancelled.
+pub async fn import() -> Result<Option<(String, Board)>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_file_dialog(Some("toml,json"), None).expect("Error opening nfd");
+        let path = match res {
+            nfd::Response::Okay(path) => path,
+            _ => return Ok(None),
+        };
+
+        let text =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let file = from_text(&text, is_json(&path))
+            .map_err(|e| format!("{} is not a valid board: {}", path, e))?;
+        if file.version > VERSION {
+            return Err(format!(
+                "{} is from a newer version of Sound Board ({})",
+                path, file.version
+            ));
+        }
+        Ok(Some((path, file.into())))
+    })
+}
+
+fn to_text(board: Board, json: bool) -> Result<String, String> {
+    let file = BoardFile::from(board);
+    if json {
+        serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+    } else {
+        // Going through a value puts the settings table after the plain values,
+        // which TOML requires, even when there are no triggers
+        toml::Value::try_from(&file)
+            .and_then(|value| toml::to_string_pretty(&value))
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn from_text(text: &str, json: bool) -> Result<BoardFile, String> {
+    if json {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+/// Write only `words`, for the manifest of sound packs.
+pub fn triggers_to_toml(words: Vec<Word>) -> Result<String, String> {
+    let file = TriggersFile {
+        version: VERSION,
+        triggers: words.into_iter().map(Into::into).collect(),
+    };
+    toml::to_string_pretty(&file).map_err(|e| e.to_string())
+}
+
+pub fn triggers_from_toml(text: &str) -> Result<Vec<Word>, String> {
+    let file: TriggersFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    Ok(file.triggers.into_iter().map(Into::into).collect())
+}
+
+fn is_json(path: &str) -> bool {
+    path.to_lowercase().ends_with(".json")
+}
+
+// The file mirrors the database, enums are written with `as_str`.
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct BoardFile {
+    version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    settings: SettingsFile,
+    triggers: Vec<TriggerFile>,
+    blacklist: Vec<BlackWordFile>,
+    policies: Vec<PolicyFile>,
+}
+
+impl Default for BoardFile {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            token: None,
+            settings: SettingsFile::default(),
+            triggers: vec![],
+            blacklist: vec![],
+            policies: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct TriggersFile {
+    version: i32,
+    triggers: Vec<TriggerFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    quiet_hours: String,
+    timezone: String,
+    history_limit: i32,
+    theme: String,
+    default_chn_id: String,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        BoardSettings::default().into()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct TriggerFile {
+    word: String,
+    mode: String,
+    chn_id: String,
+    path: String,
+    hash: String,
+    schedule: String,
+    probability: i32,
+    priority: i32,
+    condition: String,
+    source: String,
+    tts_template: String,
+}
+
+impl Default for TriggerFile {
+    fn default() -> Self {
+        Word::default().into()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct BlackWordFile {
+    word: String,
+    mode: String,
+    scope: String,
+    scope_id: String,
+    action: String,
+    sound_path: String,
+}
+
+impl Default for BlackWordFile {
+    fn default() -> Self {
+        BlackWord::default().into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct PolicyFile {
+    chn_id: String,
+    policy: String,
+}
+
+impl From<Board> for BoardFile {
+    fn from(board: Board) -> Self {
+        Self {
+            version: VERSION,
+            token: board.token,
+            settings: board.settings.into(),
+            triggers: board.words.into_iter().map(Into::into).collect(),
+            blacklist: board.blacklist.into_iter().map(Into::into).collect(),
+            policies: board.policies.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<BoardFile> for Board {
+    fn from(file: BoardFile) -> Self {
+        Self {
+            settings: file.settings.into(),
+            token: file.token,
+            words: file.triggers.into_iter().map(Into::into).collect(),
+            blacklist: file.blacklist.into_iter().map(Into::into).collect(),
+            policies: file.policies.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<BoardSettings> for SettingsFile {
+    fn from(settings: BoardSettings) -> Self {
+        Self {
+            quiet_hours: settings.quiet_hours,
+            timezone: settings.timezone,
+            history_limit: settings.history_limit,
+            theme: settings.theme.as_str().to_string(),
+            default_chn_id: settings.default_chn_id,
+        }
+    }
+}
+
+impl From<SettingsFile> for BoardSettings {
+    fn from(file: SettingsFile) -> Self {
+        Self {
+            quiet_hours: file.quiet_hours,
+            timezone: file.timezone,
+            history_limit: file.history_limit,
+            theme: Theme::from(file.theme.as_str()),
+            default_chn_id: file.default_chn_id,
+            // Where the library is depends on the machine, so it isn't shared
+            library_dir: String::new(),
+        }
+    }
+}
+
+impl From<Word> for TriggerFile {
+    fn from(word: Word) -> Self {
+        Self {
+            word: word.word,
+            mode: word.mode.as_str().to_string(),
+            chn_id: word.chn_id,
+            path: word.path,
+            hash: word.hash,
+            schedule: word.schedule,
+            probability: word.probability,
+            priority: word.priority,
+            condition: word.condition,
+            source: word.source.as_str().to_string(),
+            tts_template: word.tts_template,
+        }
+    }
+}
+
+impl From<TriggerFile> for Word {
+    fn from(file: TriggerFile) -> Self {
+        Self {
+            word: file.word,
+            mode: MatchMode::from(file.mode.as_str()),
+            chn_id: file.chn_id,
+            path: file.path,
+            hash: file.hash,
+            schedule: file.schedule,
+            probability: file.probability,
+            priority: file.priority,
+            condition: file.condition,
+            source: Source::from(file.source.as_str()),
+            tts_template: file.tts_template,
+        }
+    }
+}
+
+impl From<BlackWord> for BlackWordFile {
+    fn from(word: BlackWord) -> Self {
+        Self {
+            word: word.word,
+            mode: word.mode.as_str().to_string(),
+            scope: word.scope.as_str().to_string(),
+            scope_id: word.scope_id,
+            action: word.action.as_str().to_string(),
+            sound_path: word.sound_path,
+        }
+    }
+}
+
+impl From<BlackWordFile> for BlackWord {
+    fn from(file: BlackWordFile) -> Self {
+        Self {
+            word: file.word,
+            mode: MatchMode::from(file.mode.as_str()),
+            scope: Scope::from(file.scope.as_str()),
+            scope_id: file.scope_id,
+            action: Action::from(file.action.as_str()),
+            sound_path: file.sound_path,
+        }
+    }
+}
+
+impl From<ChannelPolicy> for PolicyFile {
+    fn from(policy: ChannelPolicy) -> Self {
+        Self {
+            chn_id: policy.chn_id,
+            policy: policy.policy.as_str().to_string(),
+        }
+    }
+}
+
+impl From<PolicyFile> for ChannelPolicy {
+    fn from(file: PolicyFile) -> Self {
+        Self {
+            chn_id: file.chn_id,
+            policy: Policy::from(file.policy.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(word: &str, chn_id: &str, path: &str) -> Word {
+        Word {
+            word: word.to_string(),
+            chn_id: chn_id.to_string(),
+            path: path.to_string(),
+            ..Word::default()
+        }
+    }
+
+    fn black_word(word: &str, scope: Scope, action: Action) -> BlackWord {
+        BlackWord {
+            word: word.to_string(),
+            scope,
+            action,
+            ..BlackWord::default()
+        }
+    }
+
+    fn channel_policy(chn_id: &str, policy: Policy) -> ChannelPolicy {
+        ChannelPolicy {
+            chn_id: chn_id.to_string(),
+            policy,
+        }
+    }
+
+    fn board() -> Board {
+        Board {
+            settings: BoardSettings {
+                quiet_hours: String::from("22:00-08:00"),
+                timezone: String::from("Europe/Athens"),
+                history_limit: 500,
+                theme: Theme::Light,
+                default_chn_id: String::from("123456789012345678"),
+                library_dir: String::new(),
+            },
+            token: Some(String::from("token")),
+            words: vec![
+                Word {
+                    mode: MatchMode::Regex,
+                    hash: String::from("af63bd4c8601b7df"),
+                    schedule: String::from("mon-fri 09:00-17:00"),
+                    probability: 40,
+                    priority: 3,
+                    ..trigger("ho+rn", "123456789012345678", "horn.mp3")
+                },
+                Word {
+                    condition: String::from("\"knock knock\" then who within 1m"),
+                    source: Source::Tts,
+                    tts_template: String::from("{author} says \"{word}\""),
+                    ..trigger("joke", "123456789012345678", "Path")
+                },
+            ],
+            blacklist: vec![
+                black_word("spoiler", Scope::Global, Action::SuppressAll),
+                BlackWord {
+                    mode: MatchMode::WholeWord,
+                    scope_id: String::from("horn"),
+                    sound_path: String::from("blocked.mp3"),
+                    ..black_word("shh", Scope::Trigger, Action::PlaySound)
+                },
+            ],
+            policies: Policy::ALL
+                .iter()
+                .map(|policy| channel_policy("123456789012345678", *policy))
+                .collect(),
+        }
+    }
+
+    fn round_trip(board: Board, json: bool) -> Board {
+        let text = to_text(board, json).unwrap();
+        from_text(&text, json).unwrap().into()
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        assert_eq!(round_trip(board(), false), board());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        assert_eq!(round_trip(board(), true), board());
+    }
+
+    #[test]
+    fn empty_board_round_trip() {
+        let empty = Board {
+            settings: BoardSettings::default(),
+            token: None,
+            words: vec![],
+            blacklist: vec![],
+            policies: vec![],
+        };
+        assert_eq!(round_trip(empty.clone(), false), empty);
+        assert_eq!(round_trip(empty.clone(), true), empty);
+    }
+
+    #[test]
+    fn library_dir_is_not_shared() {
+        let mut board = board();
+        board.settings.library_dir = String::from("/home/me/sounds");
+        assert_eq!(round_trip(board, false).settings.library_dir, "");
+    }
+
+    #[test]
+    fn missing_fields_get_their_default() {
+        let file = from_text("[[triggers]]\nword = \"horn\"\n", false).unwrap();
+        assert_eq!(file.version, VERSION);
+        let board = Board::from(file);
+        assert_eq!(board.settings, BoardSettings::default());
+        assert_eq!(
+            board.words,
+            [Word {
+                word: String::from("horn"),
+                ..Word::default()
+            }]
+        );
+    }
+
+    /// The current board, with ids from 1.
+    fn current() -> Snapshot {
+        Snapshot {
+            words: vec![
+                (1, trigger("horn", "111111111111111111", "old.mp3")),
+                (2, trigger("bye", "111111111111111111", "bye.mp3")),
+            ],
+            blacklist: vec![(3, black_word("spoiler", Scope::Global, Action::SuppressAll))],
+            policies: vec![(4, channel_policy("111111111111111111", Policy::All))],
+        }
+    }
+
+    /// A board where the first of each kind conflicts with `current`.
+    fn imported() -> Board {
+        Board {
+            settings: BoardSettings::default(),
+            token: None,
+            words: vec![
+                trigger("horn", "111111111111111111", "new.mp3"),
+                trigger("horn", "222222222222222222", "new.mp3"),
+            ],
+            blacklist: vec![
+                black_word("spoiler", Scope::Global, Action::PlaySound),
+                black_word("spoiler", Scope::Channel, Action::SuppressAll),
+            ],
+            policies: vec![
+                channel_policy("111111111111111111", Policy::Longest),
+                channel_policy("222222222222222222", Policy::HighestPriority),
+            ],
+        }
+    }
+
+    fn with_ids<T>(items: Vec<T>, first: i64) -> Vec<(i64, T)> {
+        (first..).zip(items).collect()
+    }
+
+    fn apply(mode: ImportMode) -> (Snapshot, usize) {
+        let mut snapshot = current();
+        let mut next = 10;
+        let conflicts = imported().apply(&mut snapshot, mode, || {
+            next += 1;
+            next
+        });
+        (snapshot, conflicts)
+    }
+
+    #[test]
+    fn replace_drops_the_current_board() {
+        let (snapshot, conflicts) = apply(ImportMode::Replace);
+        assert_eq!(conflicts, 0);
+
+        let board = imported();
+        assert_eq!(
+            snapshot,
+            Snapshot {
+                words: with_ids(board.words, 11),
+                blacklist: with_ids(board.blacklist, 13),
+                policies: with_ids(board.policies, 15),
+            }
+        );
+    }
+
+    #[test]
+    fn merge_keep_adds_what_doesnt_conflict() {
+        let (snapshot, conflicts) = apply(ImportMode::MergeKeep);
+        assert_eq!(conflicts, 3);
+
+        let (mut expected, board) = (current(), imported());
+        expected.words.push((11, board.words[1].clone()));
+        expected.blacklist.push((12, board.blacklist[1].clone()));
+        expected.policies.push((13, board.policies[1].clone()));
+        assert_eq!(snapshot, expected);
+    }
+
+    #[test]
+    fn merge_overwrite_replaces_what_conflicts() {
+        let (snapshot, conflicts) = apply(ImportMode::MergeOverwrite);
+        assert_eq!(conflicts, 3);
+
+        let (mut expected, board) = (current(), imported());
+        // Overwritten entries keep their ids
+        expected.words[0].1 = board.words[0].clone();
+        expected.blacklist[0].1 = board.blacklist[0].clone();
+        expected.policies[0].1 = board.policies[0].clone();
+        expected.words.push((11, board.words[1].clone()));
+        expected.blacklist.push((12, board.blacklist[1].clone()));
+        expected.policies.push((13, board.policies[1].clone()));
+        assert_eq!(snapshot, expected);
+    }
+}
@@ -1,36 +1,83 @@
-use super::word::Word;
+use super::activity::{self, Kind};
+use super::blacklist::{Action, BlackWord};
+use super::condition::MAX_WITHIN;
+use super::db;
+use super::health::Broken;
+use super::history::{Outcome, Record};
+use super::match_mode::{Pattern, Span};
+use super::player::Player;
+use super::policy::{ChannelPolicy, Policy};
+use super::schedule::{self, Schedule};
+use super::tts;
+use super::word::{Matcher, Source, Word};
 
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use rodio;
+use chrono_tz::Tz;
+use rand::{rngs::StdRng, SeedableRng};
 use serenity::{
     async_trait,
     model::{channel::Message, gateway::Ready},
     prelude::*,
 };
+use sqlx::SqlitePool;
+use tokio::task;
+
+/// How many earlier messages are kept per user.
+const MAX_RECENT: usize = 50;
 
 struct Handler; // For handling event.
-struct DevSink; // for rodio sink.
+struct Audio; // Where sounds are played.
 struct KeyWords; // The keywords to look for.
 struct Blacklist; // The keywords to look for.
-struct MsgLbl; // For messages to the user.
+struct QuietHours; // When not to play anything.
+struct Chance; // For trigger probabilities.
+struct Policies; // Which matches to play, per channel.
+struct Recent; // Earlier messages per user, for combo conditions.
+struct History; // Where to record what was played, and how many rows to keep.
+struct Events; // For messages to the user.
+struct BrokenFiles; // Sound files that can't be played, found by the health check.
+
+impl TypeMapKey for BrokenFiles {
+    type Value = Broken;
+}
 
-impl TypeMapKey for MsgLbl {
-    type Value = Arc<Mutex<String>>;
+impl TypeMapKey for Events {
+    type Value = activity::Sender;
 }
 
 impl TypeMapKey for KeyWords {
-    type Value = Arc<Mutex<Vec<Word>>>;
+    type Value = Arc<Mutex<Vec<(Word, Matcher)>>>;
 }
 
 impl TypeMapKey for Blacklist {
-    type Value = Vec<String>;
+    type Value = Vec<(BlackWord, Pattern)>;
+}
+
+impl TypeMapKey for QuietHours {
+    type Value = (Schedule, Tz);
+}
+
+impl TypeMapKey for Chance {
+    type Value = Mutex<StdRng>;
+}
+
+impl TypeMapKey for Policies {
+    type Value = HashMap<String, Policy>;
 }
 
-impl TypeMapKey for DevSink {
-    type Value = rodio::Sink;
+impl TypeMapKey for Recent {
+    type Value = Mutex<HashMap<u64, VecDeque<(Instant, String)>>>;
+}
+
+impl TypeMapKey for History {
+    type Value = (SqlitePool, i32);
+}
+
+impl TypeMapKey for Audio {
+    type Value = Arc<Player>;
 }
 
 #[async_trait]
@@ -38,92 +85,322 @@ impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
         let data = ctx.data.read().await;
 
-        let sink = data.get::<DevSink>().unwrap();
-
-        let words_arc = data.get::<KeyWords>().unwrap();
-        let words = words_arc.lock().unwrap();
-
-        let blacklist = data.get::<Blacklist>().unwrap();
-
         let mut text = msg.content.to_lowercase();
-        for embed in msg.embeds {
-            if let Some(d) = embed.description {
+        for embed in &msg.embeds {
+            if let Some(d) = &embed.description {
                 text += format!("{}\n", &d.to_lowercase()).as_ref();
             }
-            if let Some(f) = embed.footer {
+            if let Some(f) = &embed.footer {
                 text += format!("{}\n", f.text.to_lowercase()).as_ref();
             }
-            if let Some(t) = embed.title {
+            if let Some(t) = &embed.title {
                 text += format!("{}\n", &t.to_lowercase()).as_ref();
             }
-            for field in embed.fields {
+            for field in &embed.fields {
                 text += format!("{}\n{}\n", field.name, field.value).as_ref();
             }
         }
 
-        for word in blacklist.iter() {
-            if text.contains(word) {
-                let mut lbl = data.get::<MsgLbl>().unwrap().lock().unwrap();
-                *lbl = format!("Found Blacklisted \"{}\".", word);
-                return;
+        let mut records = vec![];
+        let speeches = handle(&data, &msg, &text, &mut records);
+
+        // Speech is made after the locks are released, so a slow engine doesn't hold up other messages
+        let player = data.get::<Audio>().unwrap();
+        let events = data.get::<Events>().unwrap();
+        for (index, speech) in speeches {
+            if let Err(e) = play_speech(player.clone(), speech).await {
+                events.send(Kind::Error, e);
+                records[index].outcome = Outcome::Error;
             }
         }
 
-        for word in &*words {
-            if word.chn_id != msg.channel_id.to_string() {
-                continue;
+        let (pool, limit) = data.get::<History>().unwrap();
+        if let Err(e) = db::add_history(pool, &records, *limit).await {
+            events.send(Kind::Error, e);
+        }
+    }
+
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        let data = ctx.data.read().await;
+        let events = data.get::<Events>().unwrap();
+
+        events.send(
+            Kind::Connection,
+            format!("Connected to discord as {}", ready.user.name),
+        );
+        println!("{} is connected!", ready.user.name);
+    }
+}
+
+/// Decide what to play for `msg`, whose lowercase text is `text`, and play the sound files.
+/// Every decision is added to `records`, to be saved once the locks are released.
+/// Returns the speech still to be spoken, with the index of its record.
+fn handle(
+    data: &TypeMap,
+    msg: &Message,
+    text: &str,
+    records: &mut Vec<Record>,
+) -> Vec<(usize, String)> {
+    let player = data.get::<Audio>().unwrap();
+    let events = data.get::<Events>().unwrap();
+    let broken = data.get::<BrokenFiles>().unwrap().lock().unwrap();
+
+    let words_arc = data.get::<KeyWords>().unwrap();
+    let words = words_arc.lock().unwrap();
+
+    let blacklist = data.get::<Blacklist>().unwrap();
+
+    let (quiet_hours, tz) = data.get::<QuietHours>().unwrap();
+    let now = schedule::now(*tz);
+
+    let chn_id = msg.channel_id.to_string();
+    let guild_id = msg.guild_id.map(|g| g.0);
+
+    let mut speeches = vec![];
+    let mut record = |word: &str, path: &str, outcome: Outcome| {
+        records.push(Record {
+            played_at: now.timestamp(),
+            guild_id: guild_id.map(|g| g.to_string()).unwrap_or_default(),
+            chn_id: chn_id.clone(),
+            author_id: msg.author.id.to_string(),
+            author: msg.author.name.clone(),
+            word: word.to_string(),
+            path: path.to_string(),
+            outcome,
+        });
+        records.len() - 1
+    };
+
+    let mut recent = data.get::<Recent>().unwrap().lock().unwrap();
+    let user_recent = recent.entry(msg.author.id.0).or_default();
+    let received = Instant::now();
+    while let Some((at, _)) = user_recent.front() {
+        if received.duration_since(*at) <= MAX_WITHIN && user_recent.len() < MAX_RECENT {
+            break;
+        }
+        user_recent.pop_front();
+    }
+    let earlier: Vec<(Instant, String)> = user_recent.iter().cloned().collect();
+    user_recent.push_back((received, text.to_string()));
+    drop(recent);
+
+    // Each found word with whether its schedule lets it play now
+    let found: Vec<(&Word, Span, bool)> = words
+        .iter()
+        .filter(|(word, _)| word.chn_id == chn_id)
+        .filter_map(|(word, matcher)| {
+            let span = matcher.matches(text, &earlier)?;
+            Some((word, span, matcher.is_active(&now)))
+        })
+        .collect();
+
+    if let Some((word, _, _)) = found.first() {
+        if quiet_hours.contains(&now) {
+            events.send(
+                Kind::Match,
+                format!("Suppressed \"{}\" during quiet hours.", word.word),
+            );
+            for (word, _, _) in found {
+                record(&word.word, &word.path, Outcome::Scheduled);
             }
-            if text.contains(&word.word) {
-                let mut lbl = data.get::<MsgLbl>().unwrap().lock().unwrap();
+            return speeches;
+        }
+    }
+
+    for (word, _, _) in found.iter().filter(|(_, _, active)| !active) {
+        events.send(
+            Kind::Match,
+            format!("Suppressed \"{}\" outside of its schedule.", word.word),
+        );
+        record(&word.word, &word.path, Outcome::Scheduled);
+    }
+    let matched: Vec<(&Word, Span)> = found
+        .into_iter()
+        .filter(|(_, _, active)| *active)
+        .map(|(word, span, _)| (word, span))
+        .collect();
+    let mut to_play = matched.clone();
+
+    for (black_word, pattern) in blacklist.iter() {
+        let span = match pattern.find(text) {
+            Some(span) if black_word.in_scope(guild_id, &chn_id, &matched) => span,
+            _ => continue,
+        };
 
-                *lbl = format!("Found \"{}\".", word.word);
+        events.send(
+            Kind::Blacklist,
+            format!("Found Blacklisted \"{}\".", black_word.word),
+        );
 
-                play_sound(&sink, &word.path);
+        match black_word.action {
+            Action::SuppressAll | Action::PlaySound => {
+                if matched.is_empty() {
+                    record(&black_word.word, "", Outcome::Blacklisted);
+                }
+                for (word, _) in matched.iter() {
+                    record(&word.word, &word.path, Outcome::Blacklisted);
+                }
+                if black_word.action == Action::PlaySound && !matched.is_empty() {
+                    let outcome = play_file(player, events, &black_word.sound_path);
+                    record(&black_word.word, &black_word.sound_path, outcome);
+                }
+                return speeches;
             }
+            Action::SuppressTrigger => to_play.retain(|(word, word_span)| {
+                let blocked = black_word.blocks(word, *word_span, span);
+                if blocked {
+                    record(&word.word, &word.path, Outcome::Blacklisted);
+                }
+                !blocked
+            }),
         }
     }
 
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        let data = ctx.data.read().await;
-        let mut lbl = data.get::<MsgLbl>().unwrap().lock().unwrap();
+    // Rolling first lets the policy pick among the words that would play,
+    // so an unlucky winner doesn't silence the others
+    let mut rng = data.get::<Chance>().unwrap().lock().unwrap();
+    to_play.retain(|(word, _)| {
+        let play = word.roll(&mut *rng);
+        if !play {
+            events.send(Kind::Match, format!("Skipped \"{}\" by chance.", word.word));
+            record(&word.word, &word.path, Outcome::Chance);
+        }
+        play
+    });
+    drop(rng);
 
-        *lbl = format!("Connected to discord as {}", ready.user.name);
-        println!("{} is connected!", ready.user.name);
+    let policy = data
+        .get::<Policies>()
+        .unwrap()
+        .get(&chn_id)
+        .copied()
+        .unwrap_or_default();
+
+    let chosen = policy.resolve(to_play.clone());
+    for (word, _) in to_play
+        .iter()
+        .filter(|(w, _)| !chosen.iter().any(|c| std::ptr::eq(*c, *w)))
+    {
+        record(&word.word, &word.path, Outcome::NotChosen);
     }
+
+    for word in chosen {
+        events.send(Kind::Match, format!("Found \"{}\".", word.word));
+
+        match word.source {
+            Source::File => {
+                if let Some(reason) = broken.get(&word.path) {
+                    events.send(
+                        Kind::Error,
+                        format!("Skipped \"{}\": {}", word.word, reason),
+                    );
+                    record(&word.word, &word.path, Outcome::Error);
+                    continue;
+                }
+                let outcome = play_file(player, events, &word.path);
+                record(&word.word, &word.path, outcome);
+            }
+            Source::Tts => {
+                let speech = tts::render(
+                    &word.tts_template,
+                    &[
+                        ("author", &msg.author.name),
+                        ("message", &msg.content),
+                        ("word", &word.word),
+                    ],
+                );
+                // Marked as played for now, the caller changes it if speaking fails
+                let index = record(&word.word, "", Outcome::Played);
+                speeches.push((index, speech));
+            }
+        }
+    }
+    speeches
+}
+
+fn play_file(player: &Player, events: &activity::Sender, path: &str) -> Outcome {
+    match player.play_file(path) {
+        Ok(()) => Outcome::Played,
+        Err(e) => {
+            events.send(Kind::Error, e);
+            Outcome::Error
+        }
+    }
+}
+
+/// Speak `text` through the same player as the sound files.
+async fn play_speech(player: Arc<Player>, text: String) -> Result<(), String> {
+    let wav = task::spawn_blocking(move || tts::synthesize(&text, tts::TIMEOUT))
+        .await
+        .map_err(|e| format!("Failed to speak: {}", e))??;
+    player.play_wav(wav)
 }
 
-fn play_sound(sink: &rodio::Sink, path: &str) {
-    let file = File::open(path).unwrap();
-    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
+/// Seed from `SOUND_BOARD_SEED` when it is set, so the rolls can be reproduced.
+fn new_rng() -> StdRng {
+    match std::env::var("SOUND_BOARD_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
 
-    sink.append(source);
+/// Everything the bot needs to run, besides the token.
+pub struct Config {
+    pub words: Arc<Mutex<Vec<(Word, Matcher)>>>,
+    pub blacklist: Vec<BlackWord>,
+    pub quiet_hours: Schedule,
+    pub tz: Tz,
+    pub policies: Vec<ChannelPolicy>,
+    pub pool: SqlitePool,
+    pub player: Arc<Player>,
+    pub broken: Broken,
+    /// How many rows to keep in `play_history`.
+    pub history_limit: i32,
 }
 
-pub async fn start(
-    token: String,
-    words: Arc<Mutex<Vec<Word>>>,
-    blackwords: Vec<String>,
-    msg: Arc<Mutex<String>>,
-) {
+pub async fn start(token: String, config: Config, events: activity::Sender) {
     let mut client = Client::builder(&token)
         .event_handler(Handler)
         .await
         .expect("Error creating discord client");
 
-    let device = rodio::default_output_device().unwrap();
     {
-        let sink = rodio::Sink::new(&device);
         let mut data = client.data.write().await;
-        data.insert::<DevSink>(sink);
-        data.insert::<KeyWords>(words);
-        data.insert::<Blacklist>(blackwords);
-        data.insert::<MsgLbl>(msg)
+        data.insert::<Audio>(config.player);
+        data.insert::<KeyWords>(config.words);
+        // Regexes are compiled once here rather than for every message
+        data.insert::<Blacklist>(
+            config
+                .blacklist
+                .into_iter()
+                .map(|b| {
+                    let pattern = b.pattern();
+                    (b, pattern)
+                })
+                .collect(),
+        );
+        data.insert::<QuietHours>((config.quiet_hours, config.tz));
+        data.insert::<Chance>(Mutex::new(new_rng()));
+        data.insert::<Recent>(Mutex::new(HashMap::new()));
+        data.insert::<Policies>(
+            config
+                .policies
+                .into_iter()
+                .map(|p| (p.chn_id, p.policy))
+                .collect(),
+        );
+        data.insert::<History>((config.pool, config.history_limit));
+        data.insert::<BrokenFiles>(config.broken);
+        data.insert::<Events>(events)
     }
 
     if let Err(why) = client.start().await {
         let data = client.data.read().await;
-        let mut lbl = data.get::<MsgLbl>().unwrap().lock().unwrap();
-        *lbl = "Error starting the bot.".to_string();
+        let events = data.get::<Events>().unwrap();
+        events.send(Kind::Error, "Error starting the bot.");
         println!("Client error: {:?}", why);
     };
 }
@@ -0,0 +1,577 @@
+//! Conditions for combo triggers.
+//!
+//! A condition is a small boolean expression over the words of a message:
+//!
+//! - `"good morning"` or `morning` matches when the text is in the message.
+//! - `a & b` needs both, `a | b` needs either and `!a` needs `a` to be missing.
+//! - `a then b within 30s` needs `b` in the message and `a` in an earlier message
+//!   by the same user, at most 30 seconds ago. The units are `s`, `m` and `h`.
+//! - Parentheses group, `!` binds tighter than `&`, which binds tighter than `|`.
+//!
+//! For example `(hello | hi) & !bye` or `"knock knock" then "who" within 1m`.
+//!
+//! The words are always looked for as substrings, whatever the match mode of the trigger.
+
+use super::match_mode::Span;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The longest `within` that is accepted, older messages are forgotten.
+pub const MAX_WITHIN: Duration = Duration::from_secs(60 * 60);
+
+/// How deep an expression can go, so a long or deeply nested condition
+/// can't overflow the stack while it is parsed or evaluated.
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Word(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Then {
+        first: String,
+        then: String,
+        within: Duration,
+    },
+}
+
+/// What an expression is evaluated against.
+pub struct Context<'a> {
+    /// The lowercase text of the message.
+    pub text: &'a str,
+    /// Earlier messages of the same user, with the time they were received.
+    pub recent: &'a [(Instant, String)],
+    pub now: Instant,
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &Context) -> bool {
+        match self {
+            Expr::Word(word) => ctx.text.contains(word.as_str()),
+            Expr::Not(expr) => !expr.eval(ctx),
+            Expr::And(a, b) => a.eval(ctx) && b.eval(ctx),
+            Expr::Or(a, b) => a.eval(ctx) || b.eval(ctx),
+            Expr::Then {
+                first,
+                then,
+                within,
+            } => {
+                ctx.text.contains(then.as_str())
+                    && ctx.recent.iter().any(|(at, text)| {
+                        ctx.now.duration_since(*at) <= *within && text.contains(first.as_str())
+                    })
+            }
+        }
+    }
+
+    /// Where the expression matched in the message, at the earliest of the words
+    /// that made it true. Negated words don't count, so it is `None` when only they did.
+    /// Only meaningful when `eval` is true.
+    pub fn span(&self, ctx: &Context) -> Option<Span> {
+        let find = |word: &str| ctx.text.find(word).map(|i| (i, i + word.len()));
+        let earliest = |a: Option<Span>, b: Option<Span>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        match self {
+            Expr::Word(word) => find(word),
+            Expr::Then { then, .. } => find(then),
+            Expr::Not(_) => None,
+            Expr::And(a, b) => earliest(a.span(ctx), b.span(ctx)),
+            Expr::Or(a, b) => earliest(
+                Some(a).filter(|a| a.eval(ctx)).and_then(|a| a.span(ctx)),
+                Some(b).filter(|b| b.eval(ctx)).and_then(|b| b.span(ctx)),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Character offset of the problem in the condition.
+    pub pos: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.pos + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Then,
+    Within,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "\"{}\"", w),
+            Token::And => write!(f, "'&'"),
+            Token::Or => write!(f, "'|'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Then => write!(f, "'then'"),
+            Token::Within => write!(f, "'within'"),
+        }
+    }
+}
+
+fn error<T>(pos: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        pos,
+        message: message.into(),
+    })
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '"' => {
+                let mut word = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return error(start, "Unterminated quote"),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                            word.push('"');
+                            i += 1;
+                        }
+                        Some(c) => word.push(*c),
+                    }
+                    i += 1;
+                }
+                if word.is_empty() {
+                    return error(start, "Empty quotes");
+                }
+                Token::Word(word.to_lowercase())
+            }
+            c if is_word_char(c) => {
+                while i + 1 < chars.len() && is_word_char(chars[i + 1]) {
+                    i += 1;
+                }
+                let word: String = chars[start..=i].iter().collect::<String>().to_lowercase();
+                match word.as_str() {
+                    "then" => Token::Then,
+                    "within" => Token::Within,
+                    _ => Token::Word(word),
+                }
+            }
+            c => return error(start, format!("Unexpected character '{}'", c)),
+        };
+        tokens.push((start, token));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\'' || c == '-'
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Length of the input, for errors at the end.
+    end: usize,
+    /// How deep the expression being parsed is, see `MAX_DEPTH`.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(i, _)| *i)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    /// Go one level deeper, failing past `MAX_DEPTH`.
+    fn descend(&mut self, pos: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return error(
+                pos,
+                format!(
+                    "Too long or nested too deeply, at most {} levels",
+                    MAX_DEPTH
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    // Each operator of a chain nests the ones before it one level deeper
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.descend(self.offset())?;
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.descend(self.offset())?;
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.descend(self.offset())?;
+            self.next();
+            let expr = Expr::Not(Box::new(self.unary()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        self.sequence()
+    }
+
+    fn sequence(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.atom()?;
+        if self.peek() != Some(&Token::Then) {
+            return Ok(expr);
+        }
+
+        let first = match expr {
+            Expr::Word(word) => word,
+            _ => return error(self.offset(), "'then' must follow a single word"),
+        };
+        self.next();
+
+        let offset = self.offset();
+        let then = match self.next() {
+            Some(Token::Word(word)) => word,
+            _ => return error(offset, "Expected a word after 'then'"),
+        };
+
+        let offset = self.offset();
+        if self.next() != Some(Token::Within) {
+            return error(offset, "Expected 'within' after the second word");
+        }
+
+        let offset = self.offset();
+        let within = match self.next() {
+            Some(Token::Word(duration)) => parse_duration(&duration).ok_or_else(|| ParseError {
+                pos: offset,
+                message: format!("Invalid duration \"{}\", use e.g. 30s, 5m or 1h", duration),
+            })?,
+            _ => return error(offset, "Expected a duration after 'within'"),
+        };
+        if within > MAX_WITHIN {
+            return error(offset, "The duration can't be longer than 1h");
+        }
+
+        Ok(Expr::Then {
+            first,
+            then,
+            within,
+        })
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Word(word)) => Ok(Expr::Word(word)),
+            Some(Token::Open) => {
+                self.descend(offset)?;
+                let expr = self.or()?;
+                self.depth -= 1;
+                let close = self.offset();
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => error(
+                        close,
+                        format!("Missing ')' for the '(' at character {}", offset + 1),
+                    ),
+                }
+            }
+            Some(token) => error(offset, format!("Expected a word, found {}", token)),
+            None => error(offset, "Expected a word, found the end"),
+        }
+    }
+}
+
+fn parse_duration(s: &str) -> Option<Duration> {
+    let unit = s.chars().last()?;
+    let amount: u64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    let secs = match unit {
+        's' => amount,
+        'm' => amount.checked_mul(60)?,
+        'h' => amount.checked_mul(60 * 60)?,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+/// Parse a condition, see the module documentation for the format.
+pub fn parse(s: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(s)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: s.chars().count(),
+        depth: 0,
+    };
+
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => error(parser.offset(), format!("Unexpected {}", token)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(w: &str) -> Box<Expr> {
+        Box::new(Expr::Word(w.to_string()))
+    }
+
+    fn eval(condition: &str, text: &str) -> bool {
+        let now = Instant::now();
+        let ctx = Context {
+            text,
+            recent: &[],
+            now,
+        };
+        parse(condition).unwrap().eval(&ctx)
+    }
+
+    fn parse_error(condition: &str) -> (usize, String) {
+        let e = parse(condition).unwrap_err();
+        (e.pos, e.message)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("a | b & c").unwrap(),
+            Expr::Or(word("a"), Box::new(Expr::And(word("b"), word("c"))))
+        );
+        assert!(eval("a | b & c", "a"));
+        assert!(!eval("a | b & c", "b"));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(
+            parse("!a & b").unwrap(),
+            Expr::And(Box::new(Expr::Not(word("a"))), word("b"))
+        );
+        assert!(eval("!a & b", "b"));
+        assert!(!eval("!a & b", "a b"));
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(
+            parse("(a | b) & c").unwrap(),
+            Expr::And(Box::new(Expr::Or(word("a"), word("b"))), word("c"))
+        );
+        assert!(!eval("(a | b) & c", "a"));
+        assert!(eval("(a | b) & c", "b c"));
+        assert!(eval("!(a | b)", "c"));
+    }
+
+    #[test]
+    fn quoted_words_keep_spaces() {
+        assert_eq!(parse("\"Good Morning\"").unwrap(), *word("good morning"));
+        assert!(eval("\"good morning\" & !bye", "well, good morning"));
+        assert!(!eval("\"good morning\"", "good evening, morning"));
+    }
+
+    #[test]
+    fn then_within() {
+        assert_eq!(
+            parse("knock then who within 1m").unwrap(),
+            Expr::Then {
+                first: String::from("knock"),
+                then: String::from("who"),
+                within: Duration::from_secs(60),
+            }
+        );
+
+        let expr = parse("knock then who within 30s").unwrap();
+        let start = Instant::now();
+        let recent = [(start, String::from("knock knock"))];
+        let at = |secs| Context {
+            text: "who's there",
+            recent: &recent,
+            now: start + Duration::from_secs(secs),
+        };
+        assert!(expr.eval(&at(10)));
+        assert!(expr.eval(&at(30)));
+        assert!(!expr.eval(&at(31)));
+
+        // The second word has to be in this message, the first in an earlier one
+        let ctx = Context {
+            text: "knock",
+            recent: &recent,
+            now: start,
+        };
+        assert!(!expr.eval(&ctx));
+    }
+
+    #[test]
+    fn within_is_bounded() {
+        assert!(parse("a then b within 1h").is_ok());
+        assert!(parse("a then b within 3600s").is_ok());
+        assert_eq!(
+            parse_error("a then b within 61m"),
+            (16, String::from("The duration can't be longer than 1h"))
+        );
+        assert_eq!(
+            parse_error("a then b within 99999999999999999999h"),
+            (
+                16,
+                String::from("Invalid duration \"99999999999999999999h\", use e.g. 30s, 5m or 1h")
+            )
+        );
+    }
+
+    #[test]
+    fn then_errors() {
+        assert_eq!(
+            parse_error("(a | b) then c within 1m"),
+            (8, String::from("'then' must follow a single word"))
+        );
+        assert_eq!(
+            parse_error("a then & within 1m"),
+            (7, String::from("Expected a word after 'then'"))
+        );
+        assert_eq!(
+            parse_error("a then b 1m"),
+            (9, String::from("Expected 'within' after the second word"))
+        );
+        assert_eq!(
+            parse_error("a then b within"),
+            (15, String::from("Expected a duration after 'within'"))
+        );
+        assert_eq!(
+            parse_error("a then b within 5d"),
+            (
+                16,
+                String::from("Invalid duration \"5d\", use e.g. 30s, 5m or 1h")
+            )
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            parse_error("a & \"b"),
+            (4, String::from("Unterminated quote"))
+        );
+        assert_eq!(parse_error("a & \"\""), (4, String::from("Empty quotes")));
+        assert_eq!(
+            parse_error("a $ b"),
+            (2, String::from("Unexpected character '$'"))
+        );
+        assert_eq!(
+            parse_error("(a | b"),
+            (6, String::from("Missing ')' for the '(' at character 1"))
+        );
+        assert_eq!(
+            parse_error("a &"),
+            (3, String::from("Expected a word, found the end"))
+        );
+        assert_eq!(
+            parse_error("a & | b"),
+            (4, String::from("Expected a word, found '|'"))
+        );
+        assert_eq!(parse_error("a b"), (2, String::from("Unexpected \"b\"")));
+    }
+
+    fn span(condition: &str, text: &str) -> Option<Span> {
+        let ctx = Context {
+            text,
+            recent: &[],
+            now: Instant::now(),
+        };
+        parse(condition).unwrap().span(&ctx)
+    }
+
+    #[test]
+    fn span_is_the_earliest_word_that_matched() {
+        assert_eq!(span("hi", "oh hi"), Some((3, 5)));
+        assert_eq!(span("bye & hi", "hi, bye"), Some((0, 2)));
+        // "hi" is in the message, but the branch it is in didn't match
+        assert_eq!(span("(hi & yo) | bye", "hi, bye"), Some((4, 7)));
+        assert_eq!(span("!bye & hi", "oh hi"), Some((3, 5)));
+        assert_eq!(span("!bye", "oh hi"), None);
+    }
+
+    #[test]
+    fn error_positions_count_characters() {
+        // "é" is two bytes but one character
+        let e = parse("é & $").unwrap_err();
+        assert_eq!(e.pos, 4);
+        assert_eq!(e.to_string(), "Unexpected character '$' (at character 5)");
+    }
+
+    #[test]
+    fn depth_is_capped() {
+        let nested = |n| format!("{}a{}", "(".repeat(n), ")".repeat(n));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(parse_error(&nested(MAX_DEPTH + 1)).0, MAX_DEPTH);
+        assert!(parse(&nested(100_000)).is_err());
+
+        assert!(parse(&format!("{}a", "!".repeat(MAX_DEPTH))).is_ok());
+        assert!(parse(&format!("{}a", "!".repeat(100_000))).is_err());
+
+        let chain = |n| vec!["a"; n + 1].join(" | ");
+        assert!(parse(&chain(MAX_DEPTH)).is_ok());
+        assert!(parse(&chain(MAX_DEPTH + 1)).is_err());
+
+        // Going back up a level makes room again
+        let siblings = format!("{} & {}", nested(MAX_DEPTH - 1), nested(MAX_DEPTH - 1));
+        assert!(parse(&siblings).is_ok());
+    }
+}
@@ -1,16 +1,41 @@
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 
 use super::black_word::BlackWordEntry;
+use super::blacklist::BlackWord;
 use super::entry::Entry;
-use super::schema::SCHEMA;
+use super::history::{Outcome, Record};
+use super::policy::ChannelPolicy;
+use super::policy_entry::PolicyEntry;
+use super::schema::{MIGRATIONS, SCHEMA};
+use super::settings::BoardSettings;
+use super::stats::{self, Count, Stats};
 use super::word::Word;
 
-use sqlx::{cursor::Cursor, query, row::Row, Connect, SqliteConnection, SqlitePool};
+use chrono_tz::Tz;
 
-static PATH: &str = "sqlite://app.db";
+use sqlx::{
+    cursor::Cursor, query, row::Row, sqlite::SqliteRow, Connect, SqliteConnection, SqlitePool,
+};
+
+/// The database file, set by `profile` before anything is opened.
+static PATH: RwLock<String> = RwLock::new(String::new());
+
+pub fn set_location(path: &Path) {
+    *PATH.write().unwrap() = path.to_string_lossy().into_owned();
+}
+
+/// Where the database file is.
+pub fn location() -> String {
+    PATH.read().unwrap().clone()
+}
+
+fn url() -> String {
+    format!("sqlite://{}", location())
+}
 
 pub async fn get_pool() -> SqlitePool {
-    SqlitePool::new(PATH)
+    SqlitePool::new(&url())
         .await
         .expect("Failed to create sqlite pool")
 }
@@ -20,17 +45,60 @@ pub async fn create_tables(pool: &SqlitePool) {
         .execute(pool)
         .await
         .expect("Failed to create tables");
+
+    // The cursor is read to the end, a statement left running would lock the tables
+    // that migrations drop
+    let mut version = 0;
+    let mut cur = query("PRAGMA user_version;").fetch(pool);
+    while let Some(row) = cur.next().await.expect("Failed to read schema version") {
+        version = row.get("user_version");
+    }
+    drop(cur);
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        query(migration)
+            .execute(pool)
+            .await
+            .expect("Failed to migrate tables");
+        query(&format!("PRAGMA user_version = {};", i + 1))
+            .execute(pool)
+            .await
+            .expect("Failed to update schema version");
+    }
 }
 
-pub async fn get_token() -> String {
-    let mut con = SqliteConnection::connect(PATH)
+/// The stored token, `None` when there is none.
+pub async fn get_token() -> Option<String> {
+    let mut con = SqliteConnection::connect(url())
         .await
         .expect("Failed to create connection to db");
 
     let mut cur = query("SELECT bot_token FROM settings;").fetch(&mut con);
-    match cur.next().await.expect("Failed to query the db for token") {
-        Some(row) => row.get("bot_token"),
-        None => "Bot Token".to_string(),
+    cur.next()
+        .await
+        .expect("Failed to query the db for token")
+        .and_then(|row| row.get::<Option<String>, _>("bot_token"))
+        .filter(|token| !token.is_empty())
+}
+
+pub async fn get_settings(pool: Arc<Mutex<SqlitePool>>) -> BoardSettings {
+    let pool = pool.lock().unwrap().clone();
+
+    let mut cur = query("SELECT * FROM settings;").fetch(&pool);
+    match cur
+        .next()
+        .await
+        .expect("Failed to query the db for settings")
+    {
+        Some(row) => BoardSettings {
+            quiet_hours: row.get("quiet_hours"),
+            timezone: row.get("timezone"),
+            history_limit: row.get("history_limit"),
+            theme: row.get::<String, _>("theme").as_str().into(),
+            default_chn_id: row.get("default_chn_id"),
+            library_dir: row.get("library_dir"),
+        },
+        None => BoardSettings::default(),
     }
 }
 
@@ -42,8 +110,16 @@ pub async fn get_words(pool: &SqlitePool) -> Vec<Word> {
         let mut word = Word::default();
 
         word.word = row.get("word");
+        word.mode = row.get::<String, _>("mode").as_str().into();
         word.chn_id = row.get("chn_id");
         word.path = row.get("file_path");
+        word.hash = row.get("hash");
+        word.schedule = row.get("schedule");
+        word.probability = row.get("probability");
+        word.priority = row.get("priority");
+        word.condition = row.get("condition");
+        word.source = row.get::<String, _>("source").as_str().into();
+        word.tts_template = row.get("tts_template");
 
         words.push(word)
     }
@@ -60,14 +136,21 @@ pub async fn get_entries(pool: Arc<Mutex<SqlitePool>>) -> Vec<Entry> {
     let pool = pool.lock().unwrap().clone();
     let mut entries = vec![];
 
-    let mut cur = query("SELECT * FROM words;").fetch(&pool);
+    let mut cur = query("SELECT rowid, * FROM words;").fetch(&pool);
     while let Some(e) = cur.next().await.expect("Failed to read entries cursor") {
-        let i = entries.len();
-        let mut entry = Entry::new_idle(i);
+        let mut entry = Entry::new_idle(e.get("rowid"));
 
         entry.word.word = e.get("word");
+        entry.word.mode = e.get::<String, _>("mode").as_str().into();
         entry.word.chn_id = e.get("chn_id");
         entry.word.path = e.get("file_path");
+        entry.word.hash = e.get("hash");
+        entry.word.schedule = e.get("schedule");
+        entry.word.probability = e.get("probability");
+        entry.word.priority = e.get("priority");
+        entry.word.condition = e.get("condition");
+        entry.word.source = e.get::<String, _>("source").as_str().into();
+        entry.word.tts_template = e.get("tts_template");
 
         entries.push(entry);
     }
@@ -78,74 +161,447 @@ pub async fn get_entries(pool: Arc<Mutex<SqlitePool>>) -> Vec<Entry> {
 pub async fn get_blacklist_entries(pool: Arc<Mutex<SqlitePool>>) -> Vec<BlackWordEntry> {
     let pool = pool.lock().unwrap().clone();
     let mut words = vec![];
-    let mut i = 0;
-    let mut cur = query("SELECT * FROM blacklist;").fetch(&pool);
+    let mut cur = query("SELECT rowid, * FROM blacklist;").fetch(&pool);
     while let Some(row) = cur.next().await.expect("Failed to read blacklist cursor") {
-        let mut word = BlackWordEntry::new_idle(i);
+        let mut word = BlackWordEntry::new_idle(row.get("rowid"));
 
-        word.word = row.get("word");
+        word.word = black_word_from_row(&row);
 
         words.push(word);
-        i += 1;
     }
 
     words
 }
 
-pub async fn get_blacklist(pool: &SqlitePool) -> Vec<String> {
+pub async fn get_blacklist(pool: &SqlitePool) -> Vec<BlackWord> {
     let mut words = vec![];
     let mut cur = query("SELECT * FROM blacklist;").fetch(pool);
     while let Some(row) = cur.next().await.expect("Failed to read blacklist cursor") {
-        words.push(row.get("word"));
+        words.push(black_word_from_row(&row));
     }
 
     words
 }
 
-pub async fn get_new_blacklist(pool: Arc<Mutex<SqlitePool>>) -> Vec<String> {
+fn black_word_from_row(row: &SqliteRow) -> BlackWord {
+    let mode: String = row.get("mode");
+    let scope: String = row.get("scope");
+    let action: String = row.get("action");
+
+    BlackWord {
+        word: row.get("word"),
+        mode: mode.as_str().into(),
+        scope: scope.as_str().into(),
+        scope_id: row.get("scope_id"),
+        action: action.as_str().into(),
+        sound_path: row.get("sound_path"),
+    }
+}
+
+pub async fn get_new_blacklist(pool: Arc<Mutex<SqlitePool>>) -> Vec<BlackWord> {
     let pool = pool.lock().unwrap().clone();
     get_blacklist(&pool).await
 }
 
+/// Add the records of a message to the play history, keeping only the latest `limit` rows,
+/// and count the plays for the statistics.
+pub async fn add_history(pool: &SqlitePool, records: &[Record], limit: i32) -> Result<(), String> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    for record in records.iter() {
+        query(
+            "INSERT INTO play_history \
+             (played_at, guild_id, chn_id, author_id, author, word, file_path, outcome) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(record.played_at)
+        .bind(&record.guild_id)
+        .bind(&record.chn_id)
+        .bind(&record.author_id)
+        .bind(&record.author)
+        .bind(&record.word)
+        .bind(&record.path)
+        .bind(record.outcome.as_str())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to add to the play history: {}", e))?;
+
+        if record.outcome == Outcome::Played {
+            query(
+                "INSERT INTO plays (played_at, guild_id, chn_id, author_id, author, word) \
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(record.played_at)
+            .bind(&record.guild_id)
+            .bind(&record.chn_id)
+            .bind(&record.author_id)
+            .bind(&record.author)
+            .bind(&record.word)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("Failed to count the play: {}", e))?;
+        }
+    }
+
+    query(
+        "DELETE FROM play_history WHERE rowid NOT IN \
+         (SELECT rowid FROM play_history ORDER BY rowid DESC LIMIT ?)",
+    )
+    .bind(limit)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to trim the play history: {}", e))?;
+
+    Ok(())
+}
+
+/// How many of the top triggers and users are shown.
+const TOP: i32 = 10;
+
+/// The statistics of the plays since the unix timestamp `since`,
+/// with hours and days in `tz`.
+pub async fn get_stats(pool: Arc<Mutex<SqlitePool>>, since: i64, tz: Tz) -> Stats {
+    let pool = pool.lock().unwrap().clone();
+
+    let mut stats = Stats {
+        triggers: get_counts(
+            &pool,
+            "SELECT word AS name, COUNT(*) AS plays FROM plays WHERE played_at >= ? \
+             GROUP BY word ORDER BY plays DESC, name LIMIT ?",
+            since,
+            TOP,
+        )
+        .await,
+        users: get_counts(
+            &pool,
+            "SELECT MAX(author) AS name, COUNT(*) AS plays FROM plays WHERE played_at >= ? \
+             GROUP BY author_id ORDER BY plays DESC, name LIMIT ?",
+            since,
+            TOP,
+        )
+        .await,
+        channels: get_counts(
+            &pool,
+            "SELECT chn_id AS name, COUNT(*) AS plays FROM plays WHERE played_at >= ? \
+             GROUP BY chn_id ORDER BY plays DESC, name LIMIT ?",
+            since,
+            -1,
+        )
+        .await,
+        ..Stats::default()
+    };
+
+    let mut buckets = vec![];
+    let mut cur = query(
+        "SELECT played_at / ? * ? AS start, COUNT(*) AS plays FROM plays \
+         WHERE played_at >= ? GROUP BY start ORDER BY start",
+    )
+    .bind(stats::BUCKET)
+    .bind(stats::BUCKET)
+    .bind(since)
+    .fetch(&pool);
+    while let Some(row) = cur.next().await.expect("Failed to read plays cursor") {
+        buckets.push((row.get("start"), row.get("plays")));
+    }
+    stats.set_times(&buckets, tz);
+
+    stats
+}
+
+/// Read `name` and `plays` of `sql`, which is bound to `since` and `limit`.
+/// A negative `limit` reads every row.
+async fn get_counts(pool: &SqlitePool, sql: &str, since: i64, limit: i32) -> Vec<Count> {
+    let mut counts = vec![];
+
+    let mut cur = query(sql).bind(since).bind(limit).fetch(pool);
+    while let Some(row) = cur.next().await.expect("Failed to read plays cursor") {
+        counts.push(Count {
+            name: row.get("name"),
+            plays: row.get("plays"),
+        });
+    }
+
+    counts
+}
+
+pub async fn get_policy_entries(pool: Arc<Mutex<SqlitePool>>) -> Vec<PolicyEntry> {
+    let pool = pool.lock().unwrap().clone();
+    let mut policies = vec![];
+
+    let mut cur = query("SELECT rowid, * FROM channels;").fetch(&pool);
+    while let Some(row) = cur.next().await.expect("Failed to read channels cursor") {
+        let mut entry = PolicyEntry::new_idle(row.get("rowid"));
+
+        let policy: String = row.get("policy");
+        entry.policy = ChannelPolicy {
+            chn_id: row.get("chn_id"),
+            policy: policy.as_str().into(),
+        };
+
+        policies.push(entry);
+    }
+
+    policies
+}
+
+/// `token` is stored as it is, an empty one as NULL, and `None` keeps the one that is stored.
+/// Everything is replaced in one transaction, nothing changes when it fails.
 pub async fn save(
     pool: Arc<Mutex<SqlitePool>>,
-    token: String,
+    token: Option<String>,
+    settings: BoardSettings,
     entries: Vec<Entry>,
     blacklist: Vec<BlackWordEntry>,
-) {
+    policies: Vec<PolicyEntry>,
+) -> Result<(), String> {
     let pool = pool.lock().unwrap().clone();
 
-    query("DELETE FROM settings; INSERT INTO settings (bot_token) VALUES (?);")
-        .bind(token)
-        .execute(&pool)
-        .await
-        .expect("Failed to delete and insert token");
+    let to_err = |e: sqlx::Error| format!("Failed to save: {}", e);
+    let mut tx = pool.begin().await.map_err(to_err)?;
 
-    query("DELETE FROM words;")
-        .execute(&pool)
-        .await
-        .expect("Failed to delete old words.");
+    // The settings are updated in place, so a token that isn't given stays as it is
+    query(
+        "INSERT INTO settings (bot_token) SELECT NULL WHERE NOT EXISTS (SELECT * FROM settings);",
+    )
+    .execute(&mut tx)
+    .await
+    .map_err(to_err)?;
+    if let Some(token) = token {
+        query("UPDATE settings SET bot_token = ?;")
+            .bind(Some(token).filter(|token| !token.is_empty()))
+            .execute(&mut tx)
+            .await
+            .map_err(to_err)?;
+    }
+    query(
+        "UPDATE settings SET quiet_hours = ?, timezone = ?, history_limit = ?, theme = ?, \
+         default_chn_id = ?, library_dir = ?;",
+    )
+    .bind(settings.quiet_hours)
+    .bind(settings.timezone)
+    .bind(settings.history_limit)
+    .bind(settings.theme.as_str())
+    .bind(settings.default_chn_id)
+    .bind(settings.library_dir)
+    .execute(&mut tx)
+    .await
+    .map_err(to_err)?;
 
-    query("DELETE FROM blacklist;")
-        .execute(&pool)
+    query("DELETE FROM words; DELETE FROM blacklist; DELETE FROM channels;")
+        .execute(&mut tx)
         .await
-        .expect("Failed to delete old blacklist");
+        .map_err(to_err)?;
 
     for entry in entries.iter() {
-        query("INSERT INTO words (chn_id, word, file_path) VALUES (?, ?, ?)")
-            .bind(&entry.word.chn_id)
-            .bind(&entry.word.word)
-            .bind(&entry.word.path)
-            .execute(&pool)
-            .await
-            .expect("Failed to insert new entries");
+        query(
+            "INSERT INTO words \
+             (rowid, chn_id, word, mode, file_path, hash, schedule, probability, priority, \
+             condition, source, tts_template) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(entry.id)
+        .bind(&entry.word.chn_id)
+        .bind(&entry.word.word)
+        .bind(entry.word.mode.as_str())
+        .bind(&entry.word.path)
+        .bind(&entry.word.hash)
+        .bind(&entry.word.schedule)
+        .bind(entry.word.probability)
+        .bind(entry.word.priority)
+        .bind(&entry.word.condition)
+        .bind(entry.word.source.as_str())
+        .bind(&entry.word.tts_template)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| format!("Failed to save the trigger \"{}\": {}", entry.word.word, e))?;
     }
 
     for black_word in blacklist.iter() {
-        query("INSERT INTO blacklist (word) VALUES (?)")
-            .bind(&black_word.word)
-            .execute(&pool)
+        query(
+            "INSERT INTO blacklist (rowid, word, mode, scope, scope_id, action, sound_path) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(black_word.id)
+        .bind(&black_word.word.word)
+        .bind(black_word.word.mode.as_str())
+        .bind(black_word.word.scope.as_str())
+        .bind(&black_word.word.scope_id)
+        .bind(black_word.word.action.as_str())
+        .bind(&black_word.word.sound_path)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to save the blacklisted word \"{}\": {}",
+                black_word.word.word, e
+            )
+        })?;
+    }
+
+    for entry in policies.iter() {
+        query("INSERT INTO channels (rowid, chn_id, policy) VALUES (?, ?, ?)")
+            .bind(entry.id)
+            .bind(&entry.policy.chn_id)
+            .bind(entry.policy.policy.as_str())
+            .execute(&mut tx)
             .await
-            .expect("Failed to insert new entries");
+            .map_err(|e| {
+                format!(
+                    "Failed to save the policy of channel {}: {}",
+                    entry.policy.chn_id, e
+                )
+            })?;
+    }
+
+    tx.commit().await.map_err(to_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::runtime::Runtime;
+
+    /// A new database file for a test, removed when it exists.
+    fn temp_db(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("sound_board_{}_{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn new_database_is_migrated() {
+        let path = temp_db("migrate");
+        Runtime::new().unwrap().block_on(async {
+            let pool = SqlitePool::new(&format!("sqlite://{}", path.display()))
+                .await
+                .unwrap();
+            create_tables(&pool).await;
+
+            let mut cur = query("PRAGMA user_version;").fetch(&pool);
+            let row = cur.next().await.unwrap().unwrap();
+            assert_eq!(row.get::<i32, _>("user_version"), MIGRATIONS.len() as i32);
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+
+    async fn save_token(pool: &Arc<Mutex<SqlitePool>>, token: Option<&str>) {
+        let settings = BoardSettings {
+            timezone: String::from("Europe/Athens"),
+            ..BoardSettings::default()
+        };
+        save(
+            Arc::clone(pool),
+            token.map(String::from),
+            settings,
+            vec![],
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn token_is_kept_unless_given() {
+        // The only test that uses the shared location
+        let path = temp_db("token");
+        set_location(&path);
+
+        Runtime::new().unwrap().block_on(async {
+            let pool = get_pool().await;
+            create_tables(&pool).await;
+            let pool = Arc::new(Mutex::new(pool));
+            assert_eq!(get_token().await, None);
+
+            save_token(&pool, Some("enc1:token")).await;
+            assert_eq!(get_token().await.as_deref(), Some("enc1:token"));
+
+            // A token from outside leaves the stored one alone
+            save_token(&pool, None).await;
+            assert_eq!(get_token().await.as_deref(), Some("enc1:token"));
+            assert_eq!(
+                get_settings(Arc::clone(&pool)).await.timezone,
+                "Europe/Athens"
+            );
+
+            save_token(&pool, Some("")).await;
+            assert_eq!(get_token().await, None);
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn placeholder_token_is_dropped() {
+        let path = temp_db("placeholder");
+        Runtime::new().unwrap().block_on(async {
+            let pool = SqlitePool::new(&format!("sqlite://{}", path.display()))
+                .await
+                .unwrap();
+            // What older versions saved when no token was entered
+            query(SCHEMA).execute(&pool).await.unwrap();
+            query("INSERT INTO settings (bot_token) VALUES ('Bot Token');")
+                .execute(&pool)
+                .await
+                .unwrap();
+            create_tables(&pool).await;
+
+            let mut cur = query("SELECT bot_token FROM settings;").fetch(&pool);
+            let row = cur.next().await.unwrap().unwrap();
+            assert_eq!(row.get::<Option<String>, _>("bot_token"), None);
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn record(word: &str, outcome: Outcome) -> Record {
+        Record {
+            played_at: 0,
+            guild_id: String::new(),
+            chn_id: String::from("123456789012345678"),
+            author_id: String::new(),
+            author: String::new(),
+            word: word.to_string(),
+            path: String::new(),
+            outcome,
+        }
+    }
+
+    async fn count(pool: &SqlitePool, table: &str) -> i32 {
+        let sql = format!("SELECT COUNT(*) AS rows FROM {};", table);
+        let mut cur = query(&sql).fetch(pool);
+        let rows = cur.next().await.unwrap().unwrap().get("rows");
+        rows
+    }
+
+    #[test]
+    fn history_keeps_the_latest_rows() {
+        let path = temp_db("history");
+        Runtime::new().unwrap().block_on(async {
+            let pool = SqlitePool::new(&format!("sqlite://{}", path.display()))
+                .await
+                .unwrap();
+            create_tables(&pool).await;
+
+            let records = [
+                record("a", Outcome::Played),
+                record("b", Outcome::Chance),
+                record("c", Outcome::Played),
+            ];
+            add_history(&pool, &records, 2).await.unwrap();
+            assert_eq!(count(&pool, "play_history").await, 2);
+            assert_eq!(count(&pool, "plays").await, 2);
+
+            let mut cur = query("SELECT word FROM play_history ORDER BY rowid;").fetch(&pool);
+            let mut words: Vec<String> = vec![];
+            while let Some(row) = cur.next().await.unwrap() {
+                words.push(row.get("word"));
+            }
+            assert_eq!(words, ["b", "c"]);
+        });
+        let _ = std::fs::remove_file(&path);
     }
 }
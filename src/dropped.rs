@@ -0,0 +1,35 @@
+use std::any::TypeId;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+use iced::futures::stream::{BoxStream, StreamExt};
+use iced::Subscription;
+use iced_native::subscription::{EventStream, Recipe};
+use iced_native::{window, Event, Hasher};
+
+/// Listen to files dropped on the window.
+pub fn files() -> Subscription<PathBuf> {
+    Subscription::from_recipe(DroppedFiles)
+}
+
+struct DroppedFiles;
+
+impl Recipe<Hasher, Event> for DroppedFiles {
+    type Output = PathBuf;
+
+    fn hash(&self, state: &mut Hasher) {
+        TypeId::of::<Self>().hash(state);
+    }
+
+    fn stream(self: Box<Self>, input: EventStream) -> BoxStream<'static, PathBuf> {
+        // Only the drops become messages, not every mouse move
+        input
+            .filter_map(|event| async move {
+                match event {
+                    Event::Window(window::Event::FileDropped(path)) => Some(path),
+                    _ => None,
+                }
+            })
+            .boxed()
+    }
+}
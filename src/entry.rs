@@ -1,31 +1,54 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use super::style::Theme;
-use super::word::Word;
+use super::library;
+use super::match_mode::MatchMode;
+use super::player::{self, FileInfo};
+use super::style::{Theme, ERROR_COLOR, WARNING_COLOR};
+use super::word::{keyword_from_file, Field, Source, Word};
 
-use iced::{button, text_input, Align, Button, Command, Element, Length, Row, Text, TextInput};
+use iced::{
+    button, text_input, Align, Button, Column, Command, Element, Length, Row, Text, TextInput,
+};
 use nfd;
 use tokio::task;
 
 #[derive(Debug, Clone)]
 pub struct Entry {
-    style: Theme,
-    index: usize,
+    /// The rowid of the entry in `words`, it stays the same when saving.
+    pub id: i64,
 
     pub word: Word,
+    /// The probability as typed, it is only copied to `word` when it is a valid number.
+    probability_value: String,
+    /// Same as `probability_value`, for the priority.
+    priority_value: String,
+    /// Shown next to their fields, set when editing is done.
+    errors: Vec<(Field, String)>,
+    /// Done was pressed and the sound file is being checked, nothing was changed since.
+    checking: bool,
 
     state: EntryState,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 enum EntryState {
     Idle {
         edit_btn: button::State,
+        play_btn: button::State,
     },
     Editing {
         word_in: text_input::State,
+        mode_btn: button::State,
         chn_id_in: text_input::State,
+        schedule_in: text_input::State,
+        probability_in: text_input::State,
+        priority_in: text_input::State,
+        condition_in: text_input::State,
+        source_btn: button::State,
+        tts_in: text_input::State,
         path_btn: button::State,
+        play_btn: button::State,
         done_btn: button::State,
         delete_btn: button::State,
     },
@@ -35,8 +58,16 @@ impl Default for EntryState {
     fn default() -> Self {
         Self::Editing {
             word_in: text_input::State::new(),
+            mode_btn: button::State::new(),
             chn_id_in: text_input::State::new(),
+            schedule_in: text_input::State::new(),
+            probability_in: text_input::State::new(),
+            priority_in: text_input::State::new(),
+            condition_in: text_input::State::new(),
+            source_btn: button::State::new(),
+            tts_in: text_input::State::new(),
             path_btn: button::State::new(),
+            play_btn: button::State::new(),
             done_btn: button::State::new(),
             delete_btn: button::State::new(),
         }
@@ -47,85 +78,259 @@ impl Default for EntryState {
 pub enum EntryMessage {
     ChooseFile,
     WordChanged(String),
+    NextMode,
     ChnIdChanged(String),
+    ScheduleChanged(String),
+    ProbabilityChanged(String),
+    PriorityChanged(String),
+    ConditionChanged(String),
+    NextSource,
+    TtsChanged(String),
     Edit,
     DoneEditing,
     Delete,
+    Play,
     ChoseFile(String),
+    FileAdded(library::Added),
+    /// The result of checking the file at the path, after Done.
+    FileChecked(String, Result<(), String>),
 }
 
 impl Entry {
-    pub fn new(index: usize) -> Self {
+    pub fn new(id: i64) -> Self {
         Self {
-            style: Theme::Dark,
-            index,
+            id,
             word: Word::default(),
+            probability_value: String::from("100"),
+            priority_value: String::from("0"),
+            errors: vec![],
+            checking: false,
             state: EntryState::default(),
         }
     }
 
-    pub fn new_idle(index: usize) -> Self {
+    pub fn new_idle(id: i64) -> Self {
         Self {
-            style: Theme::Dark,
-            index,
+            id,
             word: Word::default(),
+            probability_value: String::from("100"),
+            priority_value: String::from("0"),
+            errors: vec![],
+            checking: false,
             state: EntryState::Idle {
                 edit_btn: button::State::new(),
+                play_btn: button::State::new(),
             },
         }
     }
 
+    /// A new entry for the file at `path`, with its name as the word,
+    /// playing the copy in the library that `library::add_file` returned.
+    /// It is left in editing when something is wrong with it, like having the same
+    /// word and channel as one of `entries`. The command checks the file.
+    pub fn from_file(
+        id: i64,
+        path: &Path,
+        (stored, hash): (String, String),
+        chn_id: &str,
+        entries: &[Entry],
+    ) -> (Self, Command<EntryMessage>) {
+        let mut entry = Self::new(id);
+        entry.word.word = keyword_from_file(path);
+        entry.word.chn_id = chn_id.to_string();
+        entry.word.path = stored;
+        entry.word.hash = hash;
+        let duplicate = is_duplicate(entries, id, &entry.word);
+        let command = entry.done_editing(duplicate);
+        (entry, command)
+    }
+
     pub fn update(&mut self, message: EntryMessage) -> Command<EntryMessage> {
+        // A change after Done has to be checked again
+        if !matches!(
+            message,
+            EntryMessage::FileChecked(..) | EntryMessage::Play | EntryMessage::Delete
+        ) {
+            self.checking = false;
+        }
+
         match message {
-            // This is taken care of in gui.rs
-            EntryMessage::Delete => {}
-            EntryMessage::WordChanged(new) => self.word.word = new.to_lowercase(),
+            // These are taken care of in gui.rs
+            EntryMessage::Delete | EntryMessage::Play | EntryMessage::DoneEditing => {}
+            EntryMessage::WordChanged(new) => {
+                // Lowercasing a regex would change its meaning, e.g. `\W` to `\w`
+                self.word.word = match self.word.mode {
+                    MatchMode::Regex => new,
+                    _ => new.to_lowercase(),
+                }
+            }
+            EntryMessage::NextMode => {
+                self.word.mode = self.word.mode.next();
+                if self.word.mode != MatchMode::Regex {
+                    self.word.word = self.word.word.to_lowercase();
+                }
+            }
             EntryMessage::ChnIdChanged(new) => self.word.chn_id = new,
-            EntryMessage::Edit => self.state = EntryState::default(),
-            EntryMessage::DoneEditing => {
-                if self.word.is_valid() {
-                    self.state = EntryState::Idle {
-                        edit_btn: button::State::new(),
-                    }
+            EntryMessage::ScheduleChanged(new) => self.word.schedule = new,
+            EntryMessage::ProbabilityChanged(new) => {
+                if let Ok(probability) = new.trim_end_matches('%').parse() {
+                    self.word.probability = probability;
+                }
+                self.probability_value = new;
+            }
+            EntryMessage::PriorityChanged(new) => {
+                if let Ok(priority) = new.parse() {
+                    self.word.priority = priority;
                 }
+                self.priority_value = new;
+            }
+            EntryMessage::ConditionChanged(new) => self.word.condition = new,
+            EntryMessage::NextSource => self.word.source = self.word.source.next(),
+            EntryMessage::TtsChanged(new) => self.word.tts_template = new,
+            EntryMessage::Edit => {
+                self.probability_value = self.word.probability.to_string();
+                self.priority_value = self.word.priority.to_string();
+                self.state = EntryState::default();
             }
             EntryMessage::ChooseFile => {
                 return Command::perform(select_file(), EntryMessage::ChoseFile)
             }
             EntryMessage::ChoseFile(path) => {
                 if path != "-1" {
-                    self.word.path = path;
+                    return Command::perform(
+                        library::add(PathBuf::from(path)),
+                        EntryMessage::FileAdded,
+                    );
+                }
+            }
+            EntryMessage::FileAdded(Ok((path, hash))) => {
+                self.word.path = path;
+                self.word.hash = hash;
+                self.errors.retain(|(field, _)| *field != Field::Sound);
+            }
+            EntryMessage::FileAdded(Err(e)) => {
+                self.errors.retain(|(field, _)| *field != Field::Sound);
+                self.errors.push((Field::Sound, e));
+            }
+            EntryMessage::FileChecked(path, checked) => {
+                if self.checking && path == self.word.path {
+                    self.checking = false;
+                    if let Err(e) = checked {
+                        self.errors.push((Field::Sound, e));
+                    }
+                    self.stop_editing_if_valid();
                 }
             }
         }
         Command::none()
     }
 
-    pub fn view(&mut self) -> Element<EntryMessage> {
+    /// Determine if Done found something wrong that wasn't fixed yet.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Validate the entry and stop editing it when it is valid,
+    /// `duplicate` is whether another entry has the same channel and word.
+    /// A sound file is checked by the returned command, the entry is done after that.
+    pub fn done_editing(&mut self, duplicate: bool) -> Command<EntryMessage> {
+        self.errors = self.word.errors();
+        if duplicate {
+            let error = "There is already an entry for this word and channel".to_string();
+            self.errors.push((Field::Word, error));
+        }
+        if self.probability_value.trim_end_matches('%').parse() != Ok(self.word.probability) {
+            self.errors
+                .push((Field::Probability, "Not a number".to_string()));
+        }
+        if self.priority_value.parse() != Ok(self.word.priority) {
+            self.errors
+                .push((Field::Priority, "Not a number".to_string()));
+        }
+
+        if self.word.source == Source::File {
+            self.checking = true;
+            let path = self.word.path.clone();
+            return Command::perform(player::check(path.clone()), move |checked| {
+                EntryMessage::FileChecked(path.clone(), checked)
+            });
+        }
+        self.stop_editing_if_valid();
+        Command::none()
+    }
+
+    fn stop_editing_if_valid(&mut self) {
+        if self.errors.is_empty() {
+            self.state = EntryState::Idle {
+                edit_btn: button::State::new(),
+                play_btn: button::State::new(),
+            }
+        }
+    }
+
+    /// `health` is what the last health check found out about the sound file.
+    pub fn view(
+        &mut self,
+        style: Theme,
+        health: Option<&Result<FileInfo, String>>,
+    ) -> Element<EntryMessage> {
         match &mut self.state {
-            EntryState::Idle { edit_btn } => {
+            EntryState::Idle { edit_btn, play_btn } => {
                 let word_lbl = Text::new(&self.word.word);
+                let mode_lbl = Text::new(self.word.mode.label());
                 let chn_id_lbl = Text::new(&self.word.chn_id);
-                let file_name = Path::new(&self.word.path).file_name().unwrap();
-                let path_lbl = Text::new(file_name.to_string_lossy());
+                let path_lbl = match self.word.source {
+                    Source::File => Text::new(library::file_name(&self.word.path)),
+                    Source::Tts => Text::new(format!("Says \"{}\"", self.word.tts_template)),
+                };
+                let health_lbl = match (self.word.source, health) {
+                    (Source::File, Some(Ok(info))) => Text::new(info.summary()).size(16),
+                    (Source::File, Some(Err(e))) => {
+                        Text::new(format!("(!) {}", e)).color(WARNING_COLOR)
+                    }
+                    _ => Text::new(""),
+                };
+                let schedule_lbl = Text::new(&self.word.schedule);
+                let probability_lbl = Text::new(format!("{}%", self.word.probability));
+                let priority_lbl = Text::new(format!("P{}", self.word.priority));
+                let condition_lbl = Text::new(&self.word.condition);
                 let edit_btn = Button::new(edit_btn, Text::new("edit"))
                     .on_press(EntryMessage::Edit)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
+                let play_btn = Button::new(play_btn, Text::new("Play"))
+                    .on_press(EntryMessage::Play)
+                    .padding(10)
+                    .style(style);
 
                 Row::new()
                     .spacing(20)
                     .push(word_lbl)
+                    .push(mode_lbl)
                     .push(chn_id_lbl)
                     .push(path_lbl)
+                    .push(health_lbl)
+                    .push(schedule_lbl)
+                    .push(probability_lbl)
+                    .push(priority_lbl)
+                    .push(condition_lbl)
+                    .push(play_btn)
                     .push(edit_btn)
                     .align_items(Align::Center)
                     .into()
             }
             EntryState::Editing {
                 word_in,
+                mode_btn,
                 chn_id_in,
+                schedule_in,
+                probability_in,
+                priority_in,
+                condition_in,
+                source_btn,
+                tts_in,
                 path_btn,
+                play_btn,
                 done_btn,
                 delete_btn,
             } => {
@@ -133,7 +338,12 @@ impl Entry {
                     TextInput::new(word_in, "Word", &self.word.word, EntryMessage::WordChanged)
                         .padding(20)
                         .width(Length::Fill)
-                        .style(self.style);
+                        .style(style);
+
+                let mode = Button::new(mode_btn, Text::new(self.word.mode.label()))
+                    .on_press(EntryMessage::NextMode)
+                    .padding(10)
+                    .style(style);
 
                 let chn_id = TextInput::new(
                     chn_id_in,
@@ -143,38 +353,155 @@ impl Entry {
                 )
                 .padding(20)
                 .width(Length::Fill)
-                .style(self.style);
+                .style(style);
+
+                let schedule = TextInput::new(
+                    schedule_in,
+                    "Active (e.g. sat,sun 10:00-23:00)",
+                    &self.word.schedule,
+                    EntryMessage::ScheduleChanged,
+                )
+                .padding(20)
+                .width(Length::Fill)
+                .style(style);
+
+                let probability = TextInput::new(
+                    probability_in,
+                    "Chance %",
+                    &self.probability_value,
+                    EntryMessage::ProbabilityChanged,
+                )
+                .padding(20)
+                .width(Length::Units(100))
+                .style(style);
+
+                let priority = TextInput::new(
+                    priority_in,
+                    "Priority",
+                    &self.priority_value,
+                    EntryMessage::PriorityChanged,
+                )
+                .padding(20)
+                .width(Length::Units(100))
+                .style(style);
+
+                let condition = TextInput::new(
+                    condition_in,
+                    "Condition (e.g. hi & !bye)",
+                    &self.word.condition,
+                    EntryMessage::ConditionChanged,
+                )
+                .padding(20)
+                .width(Length::Fill)
+                .style(style);
 
-                let file_name = Path::new(&self.word.path).file_name().unwrap();
-                let path = Button::new(path_btn, Text::new(file_name.to_string_lossy()))
-                    .on_press(EntryMessage::ChooseFile)
+                let source = Button::new(source_btn, Text::new(self.word.source.label()))
+                    .on_press(EntryMessage::NextSource)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
 
+                let sound: Element<_> = match self.word.source {
+                    Source::File => {
+                        Button::new(path_btn, Text::new(library::file_name(&self.word.path)))
+                            .on_press(EntryMessage::ChooseFile)
+                            .padding(10)
+                            .style(style)
+                            .into()
+                    }
+                    Source::Tts => TextInput::new(
+                        tts_in,
+                        "Say (e.g. {author} said {word})",
+                        &self.word.tts_template,
+                        EntryMessage::TtsChanged,
+                    )
+                    .padding(20)
+                    .width(Length::Fill)
+                    .style(style)
+                    .into(),
+                };
+
+                let play = Button::new(play_btn, Text::new("Play"))
+                    .on_press(EntryMessage::Play)
+                    .padding(10)
+                    .style(style);
                 let done = Button::new(done_btn, Text::new("Done"))
                     .on_press(EntryMessage::DoneEditing)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
                 let delete = Button::new(delete_btn, Text::new("Delete"))
                     .on_press(EntryMessage::Delete)
                     .padding(10)
-                    .style(self.style);
+                    .style(style);
 
-                Row::new()
+                let errors = &self.errors;
+                let main_row = Row::new()
                     .spacing(20)
-                    .push(word)
-                    .push(chn_id)
-                    .push(path)
+                    .push(with_error(word, errors, Field::Word, Length::Fill))
+                    .push(mode)
+                    .push(with_error(chn_id, errors, Field::ChnId, Length::Fill))
+                    .push(source)
+                    .push(with_error(sound, errors, Field::Sound, Length::Shrink))
+                    .push(play)
                     .push(done)
                     .push(delete)
-                    .align_items(Align::Center)
+                    .align_items(Align::Center);
+
+                let options_row = Row::new()
+                    .spacing(20)
+                    .push(with_error(schedule, errors, Field::Schedule, Length::Fill))
+                    .push(with_error(
+                        probability,
+                        errors,
+                        Field::Probability,
+                        Length::Units(100),
+                    ))
+                    .push(with_error(
+                        priority,
+                        errors,
+                        Field::Priority,
+                        Length::Units(100),
+                    ))
+                    .push(with_error(
+                        condition,
+                        errors,
+                        Field::Condition,
+                        Length::Fill,
+                    ))
+                    .align_items(Align::Center);
+
+                Column::new()
+                    .spacing(10)
+                    .push(main_row)
+                    .push(options_row)
                     .into()
             }
         }
     }
 }
 
-async fn select_file() -> String {
+/// `input` with the errors of `field` under it.
+fn with_error<'a>(
+    input: impl Into<Element<'a, EntryMessage>>,
+    errors: &[(Field, String)],
+    field: Field,
+    width: Length,
+) -> Column<'a, EntryMessage> {
+    errors
+        .iter()
+        .filter(|(f, _)| *f == field)
+        .fold(Column::new().width(width).push(input), |col, (_, error)| {
+            col.push(Text::new(error).size(16).color(ERROR_COLOR))
+        })
+}
+
+/// Determine if an entry other than `id` has the same channel and word as `word`.
+pub fn is_duplicate(entries: &[Entry], id: i64, word: &Word) -> bool {
+    entries
+        .iter()
+        .any(|e| e.id != id && e.word.word == word.word && e.word.chn_id == word.chn_id)
+}
+
+pub async fn select_file() -> String {
     task::block_in_place(|| {
         let res = nfd::open_file_dialog(None, None).expect("Error opening nfd");
         return match res {
@@ -183,3 +510,92 @@ async fn select_file() -> String {
         };
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_entry() -> Entry {
+        let mut entry = Entry::new(1);
+        entry.word.word = String::from("horn");
+        entry.word.chn_id = String::from("123456789012345678");
+        entry.word.path = String::from("horn.mp3");
+        entry
+    }
+
+    fn is_idle(entry: &Entry) -> bool {
+        matches!(entry.state, EntryState::Idle { .. })
+    }
+
+    #[test]
+    fn done_waits_for_the_file_check() {
+        let mut entry = valid_entry();
+        let _ = entry.done_editing(false);
+        assert!(!is_idle(&entry));
+
+        let checked = EntryMessage::FileChecked(String::from("horn.mp3"), Ok(()));
+        let _ = entry.update(checked);
+        assert!(is_idle(&entry));
+        assert!(!entry.has_errors());
+    }
+
+    #[test]
+    fn bad_file_stays_in_editing() {
+        let mut entry = valid_entry();
+        let _ = entry.done_editing(false);
+        let checked = EntryMessage::FileChecked(String::from("horn.mp3"), Err(String::from("bad")));
+        let _ = entry.update(checked);
+        assert!(!is_idle(&entry));
+        assert_eq!(entry.errors, [(Field::Sound, String::from("bad"))]);
+    }
+
+    #[test]
+    fn check_is_ignored_after_a_change() {
+        let mut entry = valid_entry();
+        let _ = entry.done_editing(false);
+        let _ = entry.update(EntryMessage::WordChanged(String::from("air horn")));
+
+        let checked = EntryMessage::FileChecked(String::from("horn.mp3"), Ok(()));
+        let _ = entry.update(checked);
+        assert!(!is_idle(&entry));
+    }
+
+    fn from_file(id: i64, path: &str, entries: &[Entry]) -> Entry {
+        let added = (library::file_name(path), String::new());
+        let chn_id = "123456789012345678";
+        Entry::from_file(id, Path::new(path), added, chn_id, entries).0
+    }
+
+    #[test]
+    fn file_entries_are_checked_for_duplicates() {
+        let mut entries = vec![from_file(1, "/sounds/horn.mp3", &[])];
+        for (id, path) in [
+            (2, "/other/horn.wav"),
+            (3, "/sounds/bell.mp3"),
+            (4, "/sounds/bell.ogg"),
+        ]
+        .iter()
+        {
+            let entry = from_file(*id, path, &entries);
+            entries.push(entry);
+        }
+
+        let errors: Vec<bool> = entries.iter().map(Entry::has_errors).collect();
+        assert_eq!(errors, [false, true, false, true]);
+        assert_eq!(
+            entries[1].errors,
+            [(
+                Field::Word,
+                String::from("There is already an entry for this word and channel")
+            )]
+        );
+    }
+
+    #[test]
+    fn speech_is_done_right_away() {
+        let mut entry = valid_entry();
+        entry.word.source = Source::Tts;
+        let _ = entry.done_editing(false);
+        assert!(is_idle(&entry));
+    }
+}
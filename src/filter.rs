@@ -0,0 +1,100 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use super::word::Word;
+
+/// The columns the triggers can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Word,
+    Channel,
+    File,
+}
+
+impl SortBy {
+    pub const ALL: [SortBy; 3] = [SortBy::Word, SortBy::Channel, SortBy::File];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Word => "Word",
+            SortBy::Channel => "Channel Id",
+            SortBy::File => "Sound file",
+        }
+    }
+
+    fn key(self, word: &Word) -> String {
+        match self {
+            SortBy::Word => word.word.clone(),
+            SortBy::Channel => word.chn_id.clone(),
+            SortBy::File => file_name(word),
+        }
+    }
+}
+
+fn file_name(word: &Word) -> String {
+    Path::new(&word.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Which triggers are shown, and in what order.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub search: String,
+    /// `None` keeps the order they were added in.
+    pub sort_by: Option<SortBy>,
+    pub ascending: bool,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            search: String::new(),
+            sort_by: None,
+            ascending: true,
+        }
+    }
+}
+
+impl Filter {
+    /// Determine if `word` matches the search, by word, channel id or file name.
+    pub fn shows(&self, word: &Word) -> bool {
+        let search = self.search.trim().to_lowercase();
+        search.is_empty()
+            || word.word.contains(&search)
+            || word.chn_id.contains(&search)
+            || file_name(word).contains(&search)
+    }
+
+    pub fn cmp(&self, a: &Word, b: &Word) -> Ordering {
+        let ordering = match self.sort_by {
+            Some(by) => by.key(a).cmp(&by.key(b)),
+            None => Ordering::Equal,
+        };
+        if self.ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    }
+
+    /// Sort by `by`, or reverse the order when it is already sorted by it.
+    pub fn toggle(&mut self, by: SortBy) {
+        if self.sort_by == Some(by) {
+            self.ascending = !self.ascending;
+        } else {
+            self.sort_by = Some(by);
+            self.ascending = true;
+        }
+    }
+
+    /// The header of `by`, with an arrow when sorted by it.
+    pub fn header(&self, by: SortBy) -> String {
+        match self.sort_by {
+            Some(sort_by) if sort_by == by && self.ascending => format!("{} ^", by.label()),
+            Some(sort_by) if sort_by == by => format!("{} v", by.label()),
+            _ => by.label().to_string(),
+        }
+    }
+}
@@ -1,158 +1,806 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use super::activity::{self, Kind};
 use super::black_word::{BlackWordEntry, BlackWordMessage};
+use super::blacklist::BlackWord;
+use super::board_file::{self, Board, ImportMode};
 use super::bot;
 use super::db;
-use super::entry::{Entry, EntryMessage};
-use super::style::Theme;
-use super::word::Word;
+use super::dropped;
+use super::entry::{self, Entry, EntryMessage};
+use super::filter::{Filter, SortBy};
+use super::health;
+use super::import::{self, Candidate};
+use super::library::{self, Relink};
+use super::player::{self, FileInfo, Player};
+use super::policy_entry::{PolicyEntry, PolicyMessage};
+use super::profile;
+use super::schedule::{self, Schedule};
+use super::settings::{self, BoardSettings};
+use super::sound_pack;
+use super::stats::{self, Count, Range, Stats};
+use super::style::{Theme, ERROR_COLOR};
+use super::token;
+use super::undo::{History, Snapshot};
+use super::word::{self, Matcher, Source, Word};
 
+use chrono::Utc;
+use chrono_tz::Tz;
 use iced::{
-    button, scrollable, text_input, Align, Application, Button, Column, Command, Container,
-    Element, HorizontalAlignment, Length, Row, Scrollable, Settings, Space, Text, TextInput,
+    button, scrollable, text_input, Align, Application, Button, Checkbox, Column, Command,
+    Container, Element, HorizontalAlignment, Length, Row, Scrollable, Settings, Space,
+    Subscription, Text, TextInput,
 };
 use sqlx::SqlitePool;
 
-pub fn main(pool: SqlitePool, words: Vec<Word>, blackwords: Vec<String>) {
-    SoundBoard::run(Settings::with_flags((pool, words, blackwords)));
+/// How many events the log keeps, older ones are dropped.
+const LOG_SIZE: usize = 500;
+
+pub fn main(
+    pool: SqlitePool,
+    words: Vec<Word>,
+    blackwords: Vec<BlackWord>,
+    profile: Option<String>,
+) {
+    SoundBoard::run(Settings::with_flags((pool, words, blackwords, profile)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Triggers,
+    Blacklist,
+    Settings,
+    Log,
+    Statistics,
+}
+
+impl Page {
+    const ALL: [Page; 5] = [
+        Page::Triggers,
+        Page::Blacklist,
+        Page::Settings,
+        Page::Log,
+        Page::Statistics,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Page::Triggers => "Triggers",
+            Page::Blacklist => "Blacklist",
+            Page::Settings => "Settings",
+            Page::Log => "Log",
+            Page::Statistics => "Statistics",
+        }
+    }
 }
 
 struct SoundBoard {
     style: Theme,
-    message: Arc<Mutex<String>>,
+    events: activity::Sender,
+    events_receiver: activity::Receiver,
+    log: Vec<activity::Event>,
+    /// The kinds of events that are shown in the log.
+    log_filter: Vec<Kind>,
     bot_running: bool,
+    toolbar: Toolbar,
     start_bot_btn: button::State,
-    save_btn: button::State,
+    /// Quit was pressed with unsaved changes, waiting for the user to confirm.
+    confirm_quit: bool,
+    history: History,
+    /// What was last loaded or saved, to tell if there are unsaved changes.
+    saved: (Snapshot, BoardSettings, String),
+    /// What is being saved, it becomes `saved` once the save is done.
+    saving: Option<(Snapshot, BoardSettings, String)>,
     token: text_input::State,
+    token_source: token::Source,
     add_entry_btn: button::State,
     add_black_word_btn: button::State,
+    add_policy_btn: button::State,
     token_value: String,
+    theme_btn: button::State,
+    quiet_hours: text_input::State,
+    timezone: text_input::State,
+    history_limit: text_input::State,
+    history_limit_value: String,
+    default_chn_id: text_input::State,
+    /// The open profile, `None` when a database file was given instead.
+    profile: Option<String>,
+    profiles: Vec<String>,
+    profile_btns: Vec<button::State>,
+    new_profile: text_input::State,
+    new_profile_value: String,
+    create_profile_btn: button::State,
+    library_dir: text_input::State,
+    relink_btn: button::State,
+    settings: BoardSettings,
+    export_board_btn: button::State,
+    /// Whether the token is put in exported boards.
+    export_token: bool,
+    import_board_btn: button::State,
+    import_mode_btn: button::State,
+    import_mode: ImportMode,
+    export_pack_btn: button::State,
+    import_pack_btn: button::State,
+    import_btn: button::State,
+    import_pattern: text_input::State,
+    import_pattern_value: String,
+    /// The files of a folder import, shown until it is confirmed or cancelled.
+    import: Option<Vec<Candidate>>,
+    confirm_import_btn: button::State,
+    cancel_import_btn: button::State,
+    scroll_import: scrollable::State,
     scroll_entries: scrollable::State,
+    check_files_btn: button::State,
+    /// What the last health check found out about each sound file, by path.
+    health: HashMap<String, Result<FileInfo, String>>,
+    broken: health::Broken,
+    search: text_input::State,
+    sort_btns: [button::State; 3],
+    filter: Filter,
     scroll_black_entries: scrollable::State,
+    /// Scrolls the settings page, which holds the channel policies.
+    scroll_policies: scrollable::State,
+    scroll_log: scrollable::State,
 
+    page: Page,
+    stats_range_btn: button::State,
+    stats_export_btn: button::State,
+    stats_range: Range,
+    stats: Stats,
+    scroll_stats: scrollable::State,
+
+    /// The id for the next new entry, above every id that was loaded.
+    next_id: i64,
     connection_pool: Arc<Mutex<SqlitePool>>,
-    words: Arc<Mutex<Vec<Word>>>,
+    /// `None` while there is no output device.
+    player: Option<Arc<Player>>,
+    /// The saved words, shared with the bot.
+    words: Arc<Mutex<Vec<(Word, Matcher)>>>,
     entries: Vec<Entry>,
-    blacklist: Vec<String>,
+    blacklist: Vec<BlackWord>,
     blacklist_entries: Vec<BlackWordEntry>,
+    policy_entries: Vec<PolicyEntry>,
 }
 
 impl SoundBoard {
-    fn new(pool: SqlitePool, words: Vec<Word>, blackwords: Vec<String>) -> Self {
-        Self {
+    fn new(
+        pool: SqlitePool,
+        words: Vec<Word>,
+        blackwords: Vec<BlackWord>,
+        profile: Option<String>,
+    ) -> Self {
+        let (events, events_receiver) = activity::channel();
+        let profiles = profile::list();
+        let mut board = Self {
             style: Theme::Dark,
-            message: Arc::new(Mutex::new(String::new())),
+            events,
+            events_receiver,
+            log: Vec::new(),
+            log_filter: Kind::ALL.to_vec(),
             bot_running: false,
+            toolbar: Toolbar::default(),
             start_bot_btn: button::State::default(),
-            save_btn: button::State::default(),
+            confirm_quit: false,
+            history: History::default(),
+            saved: Default::default(),
+            saving: None,
             token: text_input::State::new(),
+            token_source: token::Source::None,
             add_entry_btn: button::State::default(),
             add_black_word_btn: Default::default(),
+            add_policy_btn: button::State::default(),
             token_value: String::new(),
+            theme_btn: button::State::new(),
+            quiet_hours: text_input::State::new(),
+            timezone: text_input::State::new(),
+            history_limit: text_input::State::new(),
+            history_limit_value: String::new(),
+            default_chn_id: text_input::State::new(),
+            profile,
+            profile_btns: profiles.iter().map(|_| button::State::new()).collect(),
+            profiles,
+            new_profile: text_input::State::new(),
+            new_profile_value: String::new(),
+            create_profile_btn: button::State::new(),
+            library_dir: text_input::State::new(),
+            relink_btn: button::State::new(),
+            settings: BoardSettings::default(),
+            export_board_btn: button::State::new(),
+            export_token: false,
+            import_board_btn: button::State::new(),
+            import_mode_btn: button::State::new(),
+            import_mode: ImportMode::MergeKeep,
+            export_pack_btn: button::State::new(),
+            import_pack_btn: button::State::new(),
+            import_btn: button::State::new(),
+            import_pattern: text_input::State::new(),
+            import_pattern_value: String::new(),
+            import: None,
+            confirm_import_btn: button::State::new(),
+            cancel_import_btn: button::State::new(),
+            scroll_import: scrollable::State::new(),
             scroll_entries: scrollable::State::new(),
+            check_files_btn: button::State::new(),
+            health: HashMap::new(),
+            broken: Arc::new(Mutex::new(HashMap::new())),
+            search: text_input::State::new(),
+            sort_btns: Default::default(),
+            filter: Filter::default(),
             scroll_black_entries: scrollable::State::new(),
+            scroll_policies: scrollable::State::new(),
+            scroll_log: scrollable::State::new(),
+            page: Page::Triggers,
+            stats_range_btn: button::State::new(),
+            stats_export_btn: button::State::new(),
+            stats_range: Range::default(),
+            stats: Stats::default(),
+            scroll_stats: scrollable::State::new(),
+            next_id: 1,
             connection_pool: Arc::new(Mutex::new(pool)),
-            words: Arc::new(Mutex::new(words)),
+            player: None,
+            words: Arc::new(Mutex::new(word::with_matchers(words))),
             entries: Vec::new(),
             blacklist: blackwords,
             blacklist_entries: Vec::new(),
+            policy_entries: Vec::new(),
+        };
+        // The board can still be edited without an output device
+        if let Err(e) = board.player() {
+            board.log(Kind::Error, e);
+        }
+        board
+    }
+
+    /// The player, opened when there was no output device before.
+    fn player(&mut self) -> Result<Arc<Player>, String> {
+        match &self.player {
+            Some(player) => Ok(Arc::clone(player)),
+            None => {
+                let player = Arc::new(Player::new()?);
+                self.player = Some(Arc::clone(&player));
+                Ok(player)
+            }
+        }
+    }
+
+    fn log(&mut self, kind: Kind, text: impl Into<String>) {
+        self.push_event(activity::Event::new(kind, text));
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            words: self
+                .entries
+                .iter()
+                .map(|e| (e.id, e.word.clone()))
+                .collect(),
+            blacklist: self
+                .blacklist_entries
+                .iter()
+                .map(|b| (b.id, b.word.clone()))
+                .collect(),
+            policies: self
+                .policy_entries
+                .iter()
+                .map(|p| (p.id, p.policy.clone()))
+                .collect(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.entries = snapshot
+            .words
+            .into_iter()
+            .map(|(id, word)| {
+                let mut entry = Entry::new_idle(id);
+                entry.word = word;
+                entry
+            })
+            .collect();
+        self.blacklist_entries = snapshot
+            .blacklist
+            .into_iter()
+            .map(|(id, word)| {
+                let mut entry = BlackWordEntry::new_idle(id);
+                entry.word = word;
+                entry
+            })
+            .collect();
+        self.policy_entries = snapshot
+            .policies
+            .into_iter()
+            .map(|(id, policy)| {
+                let mut entry = PolicyEntry::new_idle(id);
+                entry.policy = policy;
+                entry
+            })
+            .collect();
+    }
+
+    /// Pass an entry's message on to the entry it is for.
+    fn update_entries(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::EntryMessage(id, EntryMessage::DoneEditing) => {
+                let duplicate = match self.entries.iter().find(|e| e.id == id) {
+                    Some(entry) => self.is_duplicate(id, &entry.word),
+                    None => false,
+                };
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.id == id) {
+                    return entry
+                        .done_editing(duplicate)
+                        .map(move |msg| Message::EntryMessage(id, msg));
+                }
+            }
+            Message::EntryMessage(id, msg) => {
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.id == id) {
+                    return entry
+                        .update(msg)
+                        .map(move |msg| Message::EntryMessage(id, msg));
+                }
+            }
+            Message::BlackWordMessage(id, BlackWordMessage::DoneEditing) => {
+                let duplicate = match self.blacklist_entries.iter().find(|b| b.id == id) {
+                    Some(entry) => self.is_black_duplicate(id, &entry.word),
+                    None => false,
+                };
+                if let Some(entry) = self.blacklist_entries.iter_mut().find(|b| b.id == id) {
+                    return entry
+                        .done_editing(duplicate)
+                        .map(move |msg| Message::BlackWordMessage(id, msg));
+                }
+            }
+            Message::BlackWordMessage(id, msg) => {
+                if let Some(black_word) = self.blacklist_entries.iter_mut().find(|b| b.id == id) {
+                    return black_word
+                        .update(msg)
+                        .map(move |msg| Message::BlackWordMessage(id, msg));
+                }
+            }
+            Message::PolicyMessage(id, PolicyMessage::DoneEditing) => {
+                let duplicate = match self.policy_entries.iter().find(|p| p.id == id) {
+                    Some(entry) => self
+                        .policy_entries
+                        .iter()
+                        .any(|p| p.id != id && p.policy.chn_id == entry.policy.chn_id),
+                    None => false,
+                };
+                if let Some(entry) = self.policy_entries.iter_mut().find(|p| p.id == id) {
+                    entry.done_editing(duplicate);
+                }
+            }
+            Message::PolicyMessage(id, msg) => {
+                if let Some(policy) = self.policy_entries.iter_mut().find(|p| p.id == id) {
+                    return policy
+                        .update(msg)
+                        .map(move |msg| Message::PolicyMessage(id, msg));
+                }
+            }
+            _ => {}
+        }
+        Command::none()
+    }
+
+    /// Check the entries that are not in `before` like edited ones,
+    /// the invalid ones are left in editing. Returns how many are invalid,
+    /// and the command that checks their sound files.
+    fn check_changed(&mut self, before: &Snapshot) -> (usize, Command<Message>) {
+        let mut changed = vec![];
+        for entry in self.entries.iter() {
+            if !before.words.contains(&(entry.id, entry.word.clone())) {
+                changed.push((
+                    Message::EntryMessage(entry.id, EntryMessage::Edit),
+                    Message::EntryMessage(entry.id, EntryMessage::DoneEditing),
+                ));
+            }
+        }
+        for entry in self.blacklist_entries.iter() {
+            if !before.blacklist.contains(&(entry.id, entry.word.clone())) {
+                changed.push((
+                    Message::BlackWordMessage(entry.id, BlackWordMessage::Edit),
+                    Message::BlackWordMessage(entry.id, BlackWordMessage::DoneEditing),
+                ));
+            }
+        }
+        for entry in self.policy_entries.iter() {
+            if !before.policies.contains(&(entry.id, entry.policy.clone())) {
+                changed.push((
+                    Message::PolicyMessage(entry.id, PolicyMessage::Edit),
+                    Message::PolicyMessage(entry.id, PolicyMessage::DoneEditing),
+                ));
+            }
+        }
+        let mut commands = vec![];
+        for (edit, done) in changed {
+            self.update_entries(edit);
+            commands.push(self.update_entries(done));
+        }
+
+        let words = self
+            .entries
+            .iter()
+            .filter(|e| e.has_errors() && !before.words.contains(&(e.id, e.word.clone())));
+        let blacklist = self
+            .blacklist_entries
+            .iter()
+            .filter(|b| b.has_errors() && !before.blacklist.contains(&(b.id, b.word.clone())));
+        let policies = self
+            .policy_entries
+            .iter()
+            .filter(|p| p.has_errors() && !before.policies.contains(&(p.id, p.policy.clone())));
+        (
+            words.count() + blacklist.count() + policies.count(),
+            Command::batch(commands),
+        )
+    }
+
+    /// How many entries have errors that keep the board from being saved.
+    fn invalid_entries(&self) -> usize {
+        self.entries.iter().filter(|e| e.has_errors()).count()
+            + self
+                .blacklist_entries
+                .iter()
+                .filter(|b| b.has_errors())
+                .count()
+            + self
+                .policy_entries
+                .iter()
+                .filter(|p| p.has_errors())
+                .count()
+    }
+
+    /// Determine if an entry other than `id` has the same word and channel as `word`.
+    fn is_duplicate(&self, id: i64, word: &Word) -> bool {
+        entry::is_duplicate(&self.entries, id, word)
+    }
+
+    /// Determine if a blacklist entry other than `id` has the same word and scope as `word`.
+    fn is_black_duplicate(&self, id: i64, word: &BlackWord) -> bool {
+        self.blacklist_entries.iter().any(|b| {
+            b.id != id
+                && b.word.word == word.word
+                && b.word.scope == word.scope
+                && b.word.scope_id == word.scope_id
+        })
+    }
+
+    /// Everything that is saved, to compare with `saved`.
+    fn board_state(&self) -> (Snapshot, BoardSettings, String) {
+        (
+            self.snapshot(),
+            self.settings.clone(),
+            self.token_value.clone(),
+        )
+    }
+
+    fn mark_saved(&mut self) {
+        self.saved = self.board_state();
+    }
+
+    fn is_dirty(&self) -> bool {
+        let (entries, settings, token) = &self.saved;
+        *entries != self.snapshot() || *settings != self.settings || *token != self.token_value
+    }
+
+    fn new_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Make sure new entries don't reuse any of `ids`.
+    fn reserve_ids(&mut self, ids: impl Iterator<Item = i64>) {
+        self.next_id = ids.map(|id| id + 1).fold(self.next_id, i64::max);
+    }
+
+    fn load_stats(&self) -> Command<Message> {
+        Command::perform(
+            db::get_stats(
+                Arc::clone(&self.connection_pool),
+                self.stats_range.since(Utc::now().timestamp()),
+                schedule::parse_timezone(&self.settings.timezone).unwrap_or(Tz::UTC),
+            ),
+            Message::GotStats,
+        )
+    }
+
+    /// Run the health check on the sound files of every entry.
+    fn check_files(&self) -> Command<Message> {
+        let mut paths: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|e| e.word.source == Source::File)
+            .map(|e| e.word.path.clone())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        Command::perform(health::check(paths), Message::Checked)
+    }
+
+    /// Run the health check again on `paths` only, after their files changed.
+    fn recheck(&self, paths: Vec<String>) -> Command<Message> {
+        Command::perform(health::check(paths), Message::Rechecked)
+    }
+
+    fn push_event(&mut self, event: activity::Event) {
+        self.log.push(event);
+        if self.log.len() > LOG_SIZE {
+            self.log.remove(0);
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    GotToken(String),
+    GotToken(Result<(String, token::Source), String>),
+    GotSettings(BoardSettings),
     GotEntries(Vec<Entry>),
     GotBlackEntries(Vec<BlackWordEntry>),
+    GotPolicyEntries(Vec<PolicyEntry>),
     StartBotPressed,
     TokenChanged(String),
+    NextTheme,
+    QuietHoursChanged(String),
+    TimezoneChanged(String),
+    HistoryLimitChanged(String),
+    DefaultChnIdChanged(String),
+    SwitchProfile(String),
+    NewProfileChanged(String),
+    CreateProfile,
+    ProfileOpened(SqlitePool),
+    LibraryDirChanged(String),
+    RelinkFiles,
+    Relinked(Result<Option<Relink>, String>),
+    FileDropped(PathBuf),
+    DroppedFileAdded(PathBuf, library::Added),
+    CheckFiles,
+    Checked(Vec<(String, Result<FileInfo, String>)>),
+    Rechecked(Vec<(String, Result<FileInfo, String>)>),
+    ExportBoard,
+    ExportTokenToggled(bool),
+    BoardExported(Result<Option<String>, String>),
+    NextImportMode,
+    ImportBoard,
+    BoardImported(Result<Option<(String, Board)>, String>),
+    ExportPack,
+    PackExported(Result<Option<(String, Vec<String>)>, String>),
+    ImportPack,
+    PackImported(Result<Option<(String, Vec<Word>)>, String>),
+    ImportPatternChanged(String),
+    ImportFolder,
+    Scanned(Result<Option<Vec<Candidate>>, String>),
+    ConfirmImport,
+    Imported(Vec<(PathBuf, library::Added)>),
+    CancelImport,
     BotFailed,
     Save,
-    Saved,
+    Saved(Result<(), String>),
     AddEntry,
     AddBlackWord,
-    EntryMessage(usize, EntryMessage),
-    BlackWordMessage(usize, BlackWordMessage),
+    AddPolicy,
+    EntryMessage(i64, EntryMessage),
+    BlackWordMessage(i64, BlackWordMessage),
+    PolicyMessage(i64, PolicyMessage),
     NewWords(Vec<Word>),
-    NewBlackWords(Vec<String>),
+    NewBlackWords(Vec<BlackWord>),
+    Event(activity::Event),
+    LogFilterToggled(Kind, bool),
+    SearchChanged(String),
+    SortBy(SortBy),
+    ShowPage(Page),
+    NextStatsRange,
+    GotStats(Stats),
+    ExportStats,
+    StatsExported(Result<Option<String>, String>),
+    Previewed(Result<(), String>),
+    StopAll,
+    Undo,
+    Redo,
+    Quit,
+    QuitAnyway,
+    CancelQuit,
+}
+
+impl Message {
+    /// Determine if the message adds or deletes an entry.
+    fn starts_change(&self) -> bool {
+        matches!(
+            self,
+            Message::AddEntry
+                | Message::AddBlackWord
+                | Message::AddPolicy
+                | Message::EntryMessage(_, EntryMessage::Delete)
+                | Message::BlackWordMessage(_, BlackWordMessage::Delete)
+                | Message::PolicyMessage(_, PolicyMessage::Delete)
+        )
+    }
+
+    /// Determine if the message starts editing an entry, which may change nothing.
+    fn starts_edit(&self) -> bool {
+        matches!(
+            self,
+            Message::EntryMessage(_, EntryMessage::Edit)
+                | Message::BlackWordMessage(_, BlackWordMessage::Edit)
+                | Message::PolicyMessage(_, PolicyMessage::Edit)
+        )
+    }
+
+    fn ends_edit(&self) -> bool {
+        matches!(
+            self,
+            Message::EntryMessage(_, EntryMessage::DoneEditing)
+                | Message::BlackWordMessage(_, BlackWordMessage::DoneEditing)
+                | Message::PolicyMessage(_, PolicyMessage::DoneEditing)
+        )
+    }
 }
 
 impl Application for SoundBoard {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = (SqlitePool, Vec<Word>, Vec<String>);
+    type Flags = (SqlitePool, Vec<Word>, Vec<BlackWord>, Option<String>);
 
     fn new(
-        (pool, words, blackwords): (SqlitePool, Vec<Word>, Vec<String>),
+        (pool, words, blackwords, profile): (SqlitePool, Vec<Word>, Vec<BlackWord>, Option<String>),
     ) -> (Self, Command<Self::Message>) {
         (
-            Self::new(pool, words, blackwords),
-            Command::perform(db::get_token(), Message::GotToken),
+            Self::new(pool, words, blackwords, profile),
+            Command::perform(token::load(), Message::GotToken),
         )
     }
 
     fn title(&self) -> String {
-        String::from("Sound Board")
+        if self.is_dirty() {
+            String::from("Sound Board - unsaved changes")
+        } else {
+            String::from("Sound Board")
+        }
     }
 
     fn update(&mut self, message: Message) -> Command<Self::Message> {
+        if message.starts_change() {
+            let before = self.snapshot();
+            self.history.record(before);
+        }
+        if message.starts_edit() {
+            let before = self.snapshot();
+            self.history.begin(before);
+        }
+        if message.ends_edit() {
+            // Finishing an edit that changed nothing shouldn't leave an undo step
+            let command = self.update_entries(message);
+            let current = self.snapshot();
+            self.history.drop_if_unchanged(&current);
+            return command;
+        }
+
         match message {
-            Message::EntryMessage(i, EntryMessage::Delete) => {
-                self.entries.remove(i);
+            Message::EntryMessage(id, EntryMessage::Delete) => {
+                self.entries.retain(|e| e.id != id);
             }
-            Message::EntryMessage(i, msg) => {
-                if let Some(entry) = self.entries.get_mut(i) {
-                    return entry
-                        .update(msg)
-                        .map(move |msg| Message::EntryMessage(i, msg));
+            Message::EntryMessage(id, EntryMessage::Play) => {
+                if let Some(word) = self
+                    .entries
+                    .iter()
+                    .find(|e| e.id == id)
+                    .map(|e| e.word.clone())
+                {
+                    match self.player() {
+                        Ok(player) => {
+                            return Command::perform(
+                                player::preview(player, word),
+                                Message::Previewed,
+                            )
+                        }
+                        Err(e) => self.log(Kind::Error, e),
+                    }
                 }
             }
-            Message::BlackWordMessage(i, BlackWordMessage::Delete) => {
-                self.blacklist_entries.remove(i);
+            Message::EntryMessage(id, msg @ EntryMessage::FileAdded(Ok(_))) => {
+                let command = self.update_entries(Message::EntryMessage(id, msg));
+                let paths = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.id == id)
+                    .map(|e| e.word.path.clone())
+                    .collect();
+                return Command::batch(vec![command, self.recheck(paths)]);
             }
-            Message::BlackWordMessage(i, msg) => {
-                if let Some(black_word) = self.blacklist_entries.get_mut(i) {
-                    return black_word
-                        .update(msg)
-                        .map(move |msg| Message::BlackWordMessage(i, msg));
-                }
+            msg @ Message::EntryMessage(..) => return self.update_entries(msg),
+            Message::BlackWordMessage(id, BlackWordMessage::Delete) => {
+                self.blacklist_entries.retain(|b| b.id != id);
+            }
+            msg @ Message::BlackWordMessage(..) => return self.update_entries(msg),
+            Message::PolicyMessage(id, PolicyMessage::Delete) => {
+                self.policy_entries.retain(|p| p.id != id);
             }
-            Message::GotToken(token) => {
-                if !token.starts_with("Bot") {
-                    self.token_value = token;
+            msg @ Message::PolicyMessage(..) => return self.update_entries(msg),
+            Message::GotToken(loaded) => {
+                match loaded {
+                    Ok((token, source)) => {
+                        self.token_value = token;
+                        self.token_source = source;
+                    }
+                    Err(e) => {
+                        self.token_source = token::Source::None;
+                        self.log(Kind::Error, e);
+                    }
                 }
+                return Command::perform(
+                    db::get_settings(Arc::clone(&self.connection_pool)),
+                    Message::GotSettings,
+                );
+            }
+            Message::GotSettings(settings) => {
+                self.history_limit_value = settings.history_limit.to_string();
+                self.style = settings.theme;
+                library::set_root(&settings.library_dir);
+                self.settings = settings;
                 return Command::perform(
                     db::get_entries(Arc::clone(&self.connection_pool)),
                     Message::GotEntries,
                 );
             }
             Message::GotEntries(entries) => {
+                self.reserve_ids(entries.iter().map(|e| e.id));
                 self.entries = entries;
                 return Command::perform(
                     db::get_blacklist_entries(Arc::clone(&self.connection_pool)),
                     Message::GotBlackEntries,
                 );
             }
-            Message::GotBlackEntries(entries) => self.blacklist_entries = entries,
+            Message::GotBlackEntries(entries) => {
+                self.reserve_ids(entries.iter().map(|e| e.id));
+                self.blacklist_entries = entries;
+                return Command::perform(
+                    db::get_policy_entries(Arc::clone(&self.connection_pool)),
+                    Message::GotPolicyEntries,
+                );
+            }
+            Message::GotPolicyEntries(entries) => {
+                self.reserve_ids(entries.iter().map(|e| e.id));
+                self.policy_entries = entries;
+                self.mark_saved();
+                return self.check_files();
+            }
             Message::StartBotPressed => {
+                let quiet_hours = Schedule::parse(&self.settings.quiet_hours);
+                let tz = schedule::parse_timezone(&self.settings.timezone);
+
+                let player = self.player();
+
                 if self.bot_running {
-                    let mut lbl = self.message.lock().unwrap();
-                    *lbl = "Bot is already running".to_string();
+                    self.log(Kind::Info, "Bot is already running");
+                } else if let Err(e) = player {
+                    self.log(Kind::Error, format!("Can't start the bot: {}", e));
+                } else if let Err(e) = &quiet_hours {
+                    self.log(Kind::Error, format!("Invalid quiet hours: {}", e));
+                } else if let Err(e) = &tz {
+                    self.log(Kind::Error, e.clone());
                 } else {
-                    let mut lbl = self.message.lock().unwrap();
-                    *lbl = "Starting Bot".to_string();
+                    self.log(Kind::Connection, "Starting Bot");
                     self.bot_running = true;
+                    let config = bot::Config {
+                        words: Arc::clone(&self.words),
+                        blacklist: self
+                            .blacklist_entries
+                            .iter()
+                            .map(|b| b.word.clone())
+                            .collect(),
+                        quiet_hours: quiet_hours.unwrap_or_default(),
+                        tz: tz.unwrap(),
+                        policies: self
+                            .policy_entries
+                            .iter()
+                            .map(|p| p.policy.clone())
+                            .collect(),
+                        pool: self.connection_pool.lock().unwrap().clone(),
+                        player: player.unwrap(),
+                        broken: Arc::clone(&self.broken),
+                        history_limit: self.settings.history_limit,
+                    };
                     return Command::perform(
-                        start_bot(
-                            self.token_value.clone(),
-                            Arc::clone(&self.words),
-                            self.blacklist_entries
-                                .iter()
-                                .map(|b| b.word.to_string())
-                                .collect(),
-                            Arc::clone(&self.message),
-                        ),
+                        start_bot(self.token_value.clone(), config, self.events.clone()),
                         |_| Message::BotFailed,
                     );
                 }
@@ -160,28 +808,480 @@ impl Application for SoundBoard {
             Message::TokenChanged(new) => {
                 self.token_value = new;
             }
+            Message::NextTheme => {
+                self.settings.theme = self.settings.theme.next();
+                self.style = self.settings.theme;
+            }
+            Message::QuietHoursChanged(new) => self.settings.quiet_hours = new,
+            Message::TimezoneChanged(new) => self.settings.timezone = new,
+            Message::DefaultChnIdChanged(new) => self.settings.default_chn_id = new,
+            Message::SwitchProfile(name) => {
+                if self.bot_running {
+                    self.log(
+                        Kind::Error,
+                        "The bot is running, restart to switch profiles",
+                    );
+                } else if self.is_dirty() || self.saving.is_some() {
+                    self.log(Kind::Error, "Save your changes before switching profiles");
+                } else {
+                    profile::open(&name);
+                    self.profile = Some(name);
+                    return Command::perform(open_db(), Message::ProfileOpened);
+                }
+            }
+            Message::NewProfileChanged(new) => self.new_profile_value = new,
+            Message::CreateProfile => {
+                let name = self.new_profile_value.trim().to_string();
+                match profile::validate_name(&name) {
+                    Ok(()) => {
+                        self.new_profile_value.clear();
+                        return self.update(Message::SwitchProfile(name));
+                    }
+                    Err(e) => self.log(Kind::Error, e),
+                }
+            }
+            Message::ProfileOpened(pool) => {
+                *self.connection_pool.lock().unwrap() = pool;
+                self.profiles = profile::list();
+                self.profile_btns = self.profiles.iter().map(|_| button::State::new()).collect();
+
+                // Everything is loaded again from the new database
+                self.token_value.clear();
+                self.entries.clear();
+                self.blacklist_entries.clear();
+                self.policy_entries.clear();
+                self.history = History::default();
+                self.health.clear();
+                self.broken.lock().unwrap().clear();
+                self.import = None;
+                self.log(Kind::Info, format!("Opened {}", db::location()));
+                return Command::batch(vec![
+                    Command::perform(token::load(), Message::GotToken),
+                    Command::perform(
+                        db::get_new_words(Arc::clone(&self.connection_pool)),
+                        Message::NewWords,
+                    ),
+                ]);
+            }
+            // It is only used once saved, half typed folders shouldn't be
+            Message::LibraryDirChanged(new) => self.settings.library_dir = new,
+            Message::RelinkFiles => {
+                let words = self.snapshot().words;
+                return Command::perform(library::relink(words), Message::Relinked);
+            }
+            Message::Relinked(Ok(Some(relink))) => {
+                if relink.found.is_empty() && relink.missing.is_empty() {
+                    self.log(Kind::Info, "No files are missing");
+                    return Command::none();
+                }
+                if !relink.found.is_empty() {
+                    let before = self.snapshot();
+                    self.history.record(before);
+                }
+                for (id, path, hash) in relink.found.iter() {
+                    if let Some(entry) = self.entries.iter_mut().find(|e| e.id == *id) {
+                        entry.word.path = path.clone();
+                        entry.word.hash = hash.clone();
+                    }
+                }
+                for word in relink.missing.iter() {
+                    self.log(Kind::Error, format!("Still missing the file of {}", word));
+                }
+                self.log(
+                    Kind::Info,
+                    format!("Relinked {} missing files", relink.found.len()),
+                );
+                let paths = relink.found.into_iter().map(|(_, path, _)| path).collect();
+                return self.recheck(paths);
+            }
+            Message::Relinked(Ok(None)) => {}
+            Message::Relinked(Err(e)) => self.log(Kind::Error, e),
+            Message::FileDropped(path) => {
+                if let Err(e) = player::check_file(&path.to_string_lossy()) {
+                    self.log(Kind::Error, e);
+                    return Command::none();
+                }
+                return Command::perform(library::add(path.clone()), move |added| {
+                    Message::DroppedFileAdded(path.clone(), added)
+                });
+            }
+            Message::DroppedFileAdded(path, Err(e)) => {
+                self.log(
+                    Kind::Error,
+                    format!("Failed to add {}: {}", path.display(), e),
+                );
+            }
+            Message::DroppedFileAdded(path, Ok(added)) => {
+                let before = self.snapshot();
+                self.history.record(before);
+
+                let id = self.new_id();
+                let (entry, check) = Entry::from_file(
+                    id,
+                    &path,
+                    added,
+                    &self.settings.default_chn_id,
+                    &self.entries,
+                );
+                self.log(
+                    Kind::Info,
+                    format!("Added \"{}\" from {}", entry.word.word, path.display()),
+                );
+                let paths = vec![entry.word.path.clone()];
+                self.entries.push(entry);
+                self.filter.search.clear();
+                self.page = Page::Triggers;
+                let check = check.map(move |msg| Message::EntryMessage(id, msg));
+                return Command::batch(vec![check, self.recheck(paths)]);
+            }
+            Message::ExportBoard => {
+                let board = Board {
+                    settings: self.settings.clone(),
+                    token: Some(self.token_value.clone()).filter(|_| self.export_token),
+                    words: self.entries.iter().map(|e| e.word.clone()).collect(),
+                    blacklist: self
+                        .blacklist_entries
+                        .iter()
+                        .map(|b| b.word.clone())
+                        .collect(),
+                    policies: self
+                        .policy_entries
+                        .iter()
+                        .map(|p| p.policy.clone())
+                        .collect(),
+                };
+                return Command::perform(board_file::export(board), Message::BoardExported);
+            }
+            Message::ExportTokenToggled(export_token) => self.export_token = export_token,
+            Message::BoardExported(Ok(Some(path))) => {
+                self.log(Kind::Info, format!("Exported the board to {}", path))
+            }
+            Message::BoardExported(Ok(None)) => {}
+            Message::BoardExported(Err(e)) => self.log(Kind::Error, e),
+            Message::NextImportMode => self.import_mode = self.import_mode.next(),
+            Message::ImportBoard => {
+                return Command::perform(board_file::import(), Message::BoardImported)
+            }
+            Message::BoardImported(Ok(Some((path, board)))) => {
+                let before = self.snapshot();
+                self.history.record(before.clone());
+
+                let mode = self.import_mode;
+                // Keeping what is there keeps the settings too
+                if mode != ImportMode::MergeKeep {
+                    self.history_limit_value = board.settings.history_limit.to_string();
+                    self.style = board.settings.theme;
+                    self.settings = BoardSettings {
+                        library_dir: self.settings.library_dir.clone(),
+                        ..board.settings.clone()
+                    };
+                    // A token from outside stays, it isn't saved anyway
+                    if let Some(token) = board
+                        .token
+                        .as_ref()
+                        .filter(|_| !self.token_source.is_external())
+                    {
+                        self.token_value = token.clone();
+                    }
+                }
+
+                let mut snapshot = self.snapshot();
+                let conflicts = board.apply(&mut snapshot, mode, || self.new_id());
+                self.restore(snapshot);
+                let (invalid, checks) = self.check_changed(&before);
+                self.log(
+                    Kind::Info,
+                    format!(
+                        "Imported {} ({}), {} entries conflicted",
+                        path,
+                        mode.label(),
+                        conflicts
+                    ),
+                );
+                if invalid > 0 {
+                    self.log(
+                        Kind::Error,
+                        format!(
+                            "{} imported entries are invalid and were left in editing",
+                            invalid
+                        ),
+                    );
+                }
+                return checks;
+            }
+            Message::BoardImported(Ok(None)) => {}
+            Message::BoardImported(Err(e)) => self.log(Kind::Error, e),
+            Message::ExportPack => {
+                let words = self.entries.iter().map(|e| e.word.clone()).collect();
+                return Command::perform(sound_pack::export(words), Message::PackExported);
+            }
+            Message::PackExported(Ok(Some((path, skipped)))) => {
+                for word in skipped {
+                    self.log(Kind::Error, format!("Left out of the pack: {}", word));
+                }
+                self.log(Kind::Info, format!("Exported a sound pack to {}", path));
+            }
+            Message::PackExported(Ok(None)) => {}
+            Message::PackExported(Err(e)) => self.log(Kind::Error, e),
+            Message::ImportPack => {
+                return Command::perform(sound_pack::import(), Message::PackImported)
+            }
+            Message::PackImported(Ok(Some((path, words)))) => {
+                let before = self.snapshot();
+                self.history.record(before.clone());
+
+                // A pack only adds triggers, it never replaces the board
+                let mode = match self.import_mode {
+                    ImportMode::MergeOverwrite => ImportMode::MergeOverwrite,
+                    _ => ImportMode::MergeKeep,
+                };
+                let paths = words
+                    .iter()
+                    .filter(|w| w.source == Source::File)
+                    .map(|w| w.path.clone())
+                    .collect();
+                let board = Board {
+                    settings: self.settings.clone(),
+                    token: None,
+                    words,
+                    blacklist: vec![],
+                    policies: vec![],
+                };
+                let mut snapshot = self.snapshot();
+                let conflicts = board.apply(&mut snapshot, mode, || self.new_id());
+                self.restore(snapshot);
+                let (invalid, checks) = self.check_changed(&before);
+                self.log(
+                    Kind::Info,
+                    format!(
+                        "Imported the sound pack {} ({}), {} triggers conflicted",
+                        path,
+                        mode.label(),
+                        conflicts
+                    ),
+                );
+                if invalid > 0 {
+                    self.log(
+                        Kind::Error,
+                        format!(
+                            "{} imported entries are invalid and were left in editing",
+                            invalid
+                        ),
+                    );
+                }
+                return Command::batch(vec![checks, self.recheck(paths)]);
+            }
+            Message::PackImported(Ok(None)) => {}
+            Message::PackImported(Err(e)) => self.log(Kind::Error, e),
+            Message::CheckFiles => return self.check_files(),
+            Message::Checked(results) => {
+                let mut broken = self.broken.lock().unwrap();
+                broken.clear();
+                for (path, info) in results.iter() {
+                    if let Err(e) = info {
+                        broken.insert(path.clone(), e.clone());
+                    }
+                }
+                let count = broken.len();
+                drop(broken);
+
+                let total = results.len();
+                self.health = results.into_iter().collect();
+                if count > 0 {
+                    self.log(
+                        Kind::Error,
+                        format!("{} of {} sound files can't be played", count, total),
+                    );
+                } else {
+                    self.log(Kind::Info, format!("All {} sound files are fine", total));
+                }
+            }
+            Message::Rechecked(results) => {
+                let mut broken = self.broken.lock().unwrap();
+                for (path, info) in results.iter() {
+                    match info {
+                        Ok(_) => broken.remove(path),
+                        Err(e) => broken.insert(path.clone(), e.clone()),
+                    };
+                }
+                drop(broken);
+
+                for (path, info) in results {
+                    if let Err(e) = &info {
+                        self.log(Kind::Error, format!("{} can't be played: {}", path, e));
+                    }
+                    self.health.insert(path, info);
+                }
+            }
+            Message::ImportPatternChanged(new) => self.import_pattern_value = new,
+            Message::ImportFolder => {
+                return Command::perform(
+                    import::scan(self.import_pattern_value.clone()),
+                    Message::Scanned,
+                )
+            }
+            Message::Scanned(Ok(Some(mut candidates))) => {
+                let chn_id = &self.settings.default_chn_id;
+                for i in 0..candidates.len() {
+                    let word = &candidates[i].word;
+                    let exists = self
+                        .entries
+                        .iter()
+                        .any(|e| e.word.word == *word && e.word.chn_id == *chn_id);
+                    let earlier = candidates[..i]
+                        .iter()
+                        .any(|c| c.error.is_none() && c.word == *word);
+                    if candidates[i].error.is_some() {
+                        continue;
+                    } else if exists {
+                        candidates[i].error =
+                            Some(String::from("There is already an entry for this word"));
+                    } else if earlier {
+                        candidates[i].error = Some(String::from("Another file has the same word"));
+                    }
+                }
+                self.import = Some(candidates);
+            }
+            Message::Scanned(Ok(None)) => {}
+            Message::Scanned(Err(e)) => self.log(Kind::Error, e),
+            Message::ConfirmImport => {
+                let candidates = self.import.take().unwrap_or_default();
+                let mut paths = vec![];
+                for candidate in candidates {
+                    match candidate.error {
+                        None => paths.push(candidate.path),
+                        Some(e) => self.log(
+                            Kind::Error,
+                            format!("Skipped {}: {}", candidate.path.display(), e),
+                        ),
+                    }
+                }
+                return Command::perform(library::add_all(paths), Message::Imported);
+            }
+            Message::Imported(added) => {
+                let before = self.snapshot();
+                self.history.record(before);
+
+                let mut paths = vec![];
+                let mut checks = vec![];
+                for (path, added) in added {
+                    match added {
+                        Ok(added) => {
+                            let id = self.new_id();
+                            let (entry, check) = Entry::from_file(
+                                id,
+                                &path,
+                                added,
+                                &self.settings.default_chn_id,
+                                &self.entries,
+                            );
+                            paths.push(entry.word.path.clone());
+                            checks.push(check.map(move |msg| Message::EntryMessage(id, msg)));
+                            self.entries.push(entry);
+                        }
+                        Err(e) => {
+                            self.log(Kind::Error, format!("Skipped {}: {}", path.display(), e))
+                        }
+                    }
+                }
+                self.log(Kind::Info, format!("Imported {} sounds", paths.len()));
+                self.filter.search.clear();
+                checks.push(self.recheck(paths));
+                return Command::batch(checks);
+            }
+            Message::CancelImport => self.import = None,
+            Message::HistoryLimitChanged(new) => {
+                if let Ok(limit) = settings::parse_history_limit(&new) {
+                    self.settings.history_limit = limit;
+                }
+                self.history_limit_value = new;
+            }
+            Message::Save if self.saving.is_some() => {
+                self.log(Kind::Info, "Still saving, try again when it is done");
+            }
+            Message::Save if self.invalid_entries() > 0 => {
+                self.log(
+                    Kind::Error,
+                    format!(
+                        "Not saved, {} entries have errors. Fix or delete them first",
+                        self.invalid_entries()
+                    ),
+                );
+            }
+            Message::Save if settings::parse_history_limit(&self.history_limit_value).is_err() => {
+                self.log(Kind::Error, "Not saved, the history size is invalid");
+            }
             Message::Save => {
+                // A token from outside is never written to the database
+                let token = if self.token_source.is_external() {
+                    None
+                } else {
+                    match token::encrypt(&self.token_value) {
+                        Ok(encrypted) => Some(encrypted),
+                        Err(e) => {
+                            self.log(Kind::Error, e);
+                            return Command::none();
+                        }
+                    }
+                };
+
+                self.saving = Some(self.board_state());
+                self.confirm_quit = false;
                 return Command::perform(
                     db::save(
                         Arc::clone(&self.connection_pool),
-                        self.token_value.clone(),
+                        token,
+                        self.settings.clone(),
                         self.entries.clone(),
                         self.blacklist_entries.clone(),
+                        self.policy_entries.clone(),
                     ),
-                    |_| Message::Saved,
+                    Message::Saved,
                 );
             }
-            Message::Saved => {
-                let mut lbl = self.message.lock().unwrap();
-                *lbl = "Saved".to_string();
-                return Command::perform(
+            Message::Saved(Err(e)) => {
+                self.saving = None;
+                self.log(Kind::Error, e);
+            }
+            Message::Saved(Ok(())) => {
+                if let Some(saved) = self.saving.take() {
+                    if !self.token_source.is_external() {
+                        self.token_source = if saved.2.is_empty() {
+                            token::Source::None
+                        } else {
+                            token::Source::Database
+                        };
+                    }
+                    self.saved = saved;
+                }
+                self.log(Kind::Info, "Saved");
+
+                // The library only moves once the new folder is in the database
+                let old_root = library::root();
+                library::set_root(&self.saved.1.library_dir);
+                let root = library::root();
+                let mut commands = vec![Command::perform(
                     db::get_new_words(Arc::clone(&self.connection_pool)),
                     Message::NewWords,
-                );
+                )];
+                if root != old_root {
+                    self.log(
+                        Kind::Info,
+                        format!(
+                            "The sound library is now {}, the files in {} were not moved. \
+                             Move them there or relink them",
+                            root.display(),
+                            old_root.display()
+                        ),
+                    );
+                    // Relative paths now point into the new folder
+                    commands.push(self.check_files());
+                }
+                return Command::batch(commands);
             }
             Message::NewWords(new_words) => {
                 let mut words = self.words.lock().unwrap();
-                *words = new_words;
+                *words = word::with_matchers(new_words);
                 return Command::perform(
                     db::get_new_blacklist(Arc::clone(&self.connection_pool)),
                     Message::NewBlackWords,
@@ -189,166 +1289,854 @@ impl Application for SoundBoard {
             }
             Message::NewBlackWords(new_words) => self.blacklist = new_words,
             Message::AddEntry => {
-                let index = self.entries.len();
-                let entry = Entry::new(index);
+                // Otherwise the new entry could be hidden by the search
+                self.filter.search.clear();
+                let entry = Entry::new(self.new_id());
                 self.entries.push(entry);
             }
             Message::AddBlackWord => {
-                let index = self.blacklist.len();
-                let entry = BlackWordEntry::new(index);
+                let entry = BlackWordEntry::new(self.new_id());
                 self.blacklist_entries.push(entry);
             }
+            Message::AddPolicy => {
+                let entry = PolicyEntry::new(self.new_id());
+                self.policy_entries.push(entry);
+            }
             Message::BotFailed => {
-                let mut lbl = self.message.lock().unwrap();
-                *lbl = "Failed to start the bot. Make sure you have the correct token".to_string();
+                self.log(
+                    Kind::Error,
+                    "Failed to start the bot. Make sure you have the correct token",
+                );
                 self.bot_running = false;
             }
+            Message::Event(event) => self.push_event(event),
+            Message::SearchChanged(new) => self.filter.search = new,
+            Message::SortBy(by) => self.filter.toggle(by),
+            Message::ShowPage(page) => {
+                self.page = page;
+                if page == Page::Statistics {
+                    return self.load_stats();
+                }
+            }
+            Message::NextStatsRange => {
+                self.stats_range = self.stats_range.next();
+                return self.load_stats();
+            }
+            Message::GotStats(stats) => self.stats = stats,
+            Message::ExportStats => {
+                return Command::perform(stats::export(self.stats.clone()), Message::StatsExported)
+            }
+            Message::StatsExported(Ok(Some(path))) => {
+                self.log(Kind::Info, format!("Exported statistics to {}", path))
+            }
+            Message::StatsExported(Ok(None)) => {}
+            Message::StatsExported(Err(e)) => self.log(Kind::Error, e),
+            Message::Previewed(Ok(())) => {}
+            Message::Previewed(Err(e)) => self.log(Kind::Error, e),
+            Message::StopAll => {
+                if let Some(player) = &self.player {
+                    player.stop_all();
+                }
+            }
+            Message::Undo => {
+                let current = self.snapshot();
+                if let Some(before) = self.history.undo(current) {
+                    self.restore(before);
+                }
+            }
+            Message::Redo => {
+                let current = self.snapshot();
+                if let Some(after) = self.history.redo(current) {
+                    self.restore(after);
+                }
+            }
+            // Quitting in the middle of a save would lose the board
+            Message::Quit | Message::QuitAnyway if self.saving.is_some() => {
+                self.log(Kind::Info, "Still saving, quit when it is done");
+            }
+            Message::Quit if self.is_dirty() => self.confirm_quit = true,
+            Message::Quit | Message::QuitAnyway => std::process::exit(0),
+            Message::CancelQuit => self.confirm_quit = false,
+            Message::LogFilterToggled(kind, shown) => {
+                self.log_filter.retain(|k| *k != kind);
+                if shown {
+                    self.log_filter.push(kind);
+                }
+            }
         }
         Command::none()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch(vec![
+            activity::subscription(Arc::clone(&self.events_receiver)).map(Message::Event),
+            dropped::files().map(Message::FileDropped),
+        ])
+    }
+
     fn view(&mut self) -> Element<Message> {
+        let status = Status {
+            page: self.page,
+            dirty: self.is_dirty(),
+            can_undo: self.history.can_undo(),
+            can_redo: self.history.can_redo(),
+            confirm_quit: self.confirm_quit,
+            style: self.style,
+        };
+
+        match self.page {
+            Page::Triggers if self.import.is_some() => self.import_page(status),
+            Page::Triggers => self.triggers_page(status),
+            Page::Blacklist => self.blacklist_page(status),
+            Page::Settings => self.settings_page(status),
+            Page::Log => self.log_page(status),
+            Page::Statistics => self.stats_page(status),
+        }
+    }
+}
+
+/// What the toolbar shows, read before the page borrows the board.
+struct Status {
+    page: Page,
+    dirty: bool,
+    can_undo: bool,
+    can_redo: bool,
+    confirm_quit: bool,
+    style: Theme,
+}
+
+/// The tabs and the buttons that are on every page.
+#[derive(Default)]
+struct Toolbar {
+    page_btns: [button::State; 5],
+    save_btn: button::State,
+    undo_btn: button::State,
+    redo_btn: button::State,
+    stop_all_btn: button::State,
+    quit_btn: button::State,
+    quit_anyway_btn: button::State,
+    cancel_quit_btn: button::State,
+}
+
+impl Toolbar {
+    /// Put the toolbar above `page`.
+    fn view<'a>(&'a mut self, status: Status, page: Element<'a, Message>) -> Element<'a, Message> {
+        let style = status.style;
+
+        let tabs = Page::ALL.iter().zip(self.page_btns.iter_mut()).fold(
+            Row::new().spacing(10),
+            |row, (page, state)| {
+                let mut tab = Button::new(state, Text::new(page.label()))
+                    .padding(15)
+                    .style(style);
+                // The current page's tab is disabled, to show where we are
+                if *page != status.page {
+                    tab = tab.on_press(Message::ShowPage(*page));
+                }
+                row.push(tab)
+            },
+        );
+
+        let save_btn = Button::new(&mut self.save_btn, Text::new("Save"))
+            .on_press(Message::Save)
+            .padding(15)
+            .style(style);
+
+        let mut undo_btn = Button::new(&mut self.undo_btn, Text::new("Undo"))
+            .padding(15)
+            .style(style);
+        if status.can_undo {
+            undo_btn = undo_btn.on_press(Message::Undo);
+        }
+
+        let mut redo_btn = Button::new(&mut self.redo_btn, Text::new("Redo"))
+            .padding(15)
+            .style(style);
+        if status.can_redo {
+            redo_btn = redo_btn.on_press(Message::Redo);
+        }
+
+        let stop_all_btn = Button::new(&mut self.stop_all_btn, Text::new("Stop all"))
+            .on_press(Message::StopAll)
+            .padding(15)
+            .style(style);
+
+        let quit_btn = Button::new(&mut self.quit_btn, Text::new("Quit"))
+            .on_press(Message::Quit)
+            .padding(15)
+            .style(style);
+
+        let unsaved = if status.dirty { "Unsaved changes" } else { "" };
+
+        let toolbar = Row::new()
+            .spacing(10)
+            .push(tabs)
+            .push(Space::with_width(Length::Fill))
+            .push(Text::new(unsaved).color(ERROR_COLOR))
+            .push(save_btn)
+            .push(undo_btn)
+            .push(redo_btn)
+            .push(stop_all_btn)
+            .push(quit_btn)
+            .align_items(Align::Center);
+
+        let confirm_quit: Element<_> = if status.confirm_quit {
+            Row::new()
+                .spacing(20)
+                .push(Text::new("There are unsaved changes, quit anyway?").color(ERROR_COLOR))
+                .push(
+                    Button::new(&mut self.quit_anyway_btn, Text::new("Quit without saving"))
+                        .on_press(Message::QuitAnyway)
+                        .padding(15)
+                        .style(style),
+                )
+                .push(
+                    Button::new(&mut self.cancel_quit_btn, Text::new("Cancel"))
+                        .on_press(Message::CancelQuit)
+                        .padding(15)
+                        .style(style),
+                )
+                .align_items(Align::Center)
+                .into()
+        } else {
+            Space::with_height(Length::Shrink).into()
+        };
+
+        Container::new(
+            Column::new()
+                .padding(20)
+                .spacing(10)
+                .push(toolbar)
+                .push(confirm_quit)
+                .push(page),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(style)
+        .into()
+    }
+}
+
+impl SoundBoard {
+    fn triggers_page(&mut self, status: Status) -> Element<Message> {
+        let style = status.style;
+
         let add_entry = Button::new(&mut self.add_entry_btn, Text::new("Add Entry"))
             .on_press(Message::AddEntry)
-            .padding(20)
-            .style(self.style);
+            .padding(15)
+            .style(style);
 
-        let add_black_word = Button::new(
-            &mut self.add_black_word_btn,
-            Text::new("Add Black Listed Word"),
+        let check_files = Button::new(&mut self.check_files_btn, Text::new("Check Files"))
+            .on_press(Message::CheckFiles)
+            .padding(15)
+            .style(style);
+
+        let import_pattern = TextInput::new(
+            &mut self.import_pattern,
+            "File pattern, like horn_*",
+            &self.import_pattern_value,
+            Message::ImportPatternChanged,
         )
-        .on_press(Message::AddBlackWord)
-        .padding(20)
-        .style(self.style);
+        .padding(10)
+        .width(Length::Units(200))
+        .style(style);
 
-        let lbl = self.message.lock().unwrap();
-        let messages_lbl = Text::new(lbl.clone()).size(20);
+        let import_btn = Button::new(&mut self.import_btn, Text::new("Import Folder"))
+            .on_press(Message::ImportFolder)
+            .padding(15)
+            .style(style);
 
-        let bot_btn = Button::new(&mut self.start_bot_btn, Text::new("Start Bot"))
-            .on_press(Message::StartBotPressed)
-            .padding(20)
-            .style(self.style);
-
-        let token_input = TextInput::new(
-            &mut self.token,
-            "Bot Token",
-            &self.token_value,
-            Message::TokenChanged,
+        let filter = &self.filter;
+        let search = TextInput::new(
+            &mut self.search,
+            "Search words, channels and files",
+            &filter.search,
+            Message::SearchChanged,
         )
-        .password()
-        .padding(20)
-        .style(self.style);
+        .padding(10)
+        .width(Length::Units(300))
+        .style(style);
 
-        let save_btn = Button::new(&mut self.save_btn, Text::new("Save"))
-            .on_press(Message::Save)
-            .padding(20)
-            .style(self.style);
+        let total = self.entries.len();
+        let mut shown: Vec<&mut Entry> = self
+            .entries
+            .iter_mut()
+            .filter(|entry| filter.shows(&entry.word))
+            .collect();
+        // Stable, so entries that compare equal keep the order they were added in
+        shown.sort_by(|a, b| filter.cmp(&a.word, &b.word));
+        let shown_lbl = Text::new(format!("Showing {} of {}", shown.len(), total));
+
+        let head = SortBy::ALL
+            .iter()
+            .zip(self.sort_btns.iter_mut())
+            .fold(Row::new().spacing(20), |row, (by, state)| {
+                row.push(
+                    Button::new(state, Text::new(filter.header(*by)))
+                        .on_press(Message::SortBy(*by))
+                        .padding(10)
+                        .style(style),
+                )
+            })
+            .push(search)
+            .push(shown_lbl)
+            .push(Space::with_width(Length::Fill))
+            .push(check_files)
+            .push(import_pattern)
+            .push(import_btn)
+            .push(add_entry)
+            .align_items(Align::Center);
+
+        let health = &self.health;
+        let entries: Element<_> = if !shown.is_empty() {
+            shown
+                .into_iter()
+                .fold(Column::new().spacing(20), |col, entry| {
+                    let id = entry.id;
+                    let file_health = health.get(&entry.word.path);
+                    col.push(
+                        entry
+                            .view(style, file_health)
+                            .map(move |message| Message::EntryMessage(id, message)),
+                    )
+                })
+                .align_items(Align::Center)
+                .into()
+        } else if total > 0 {
+            empty_lbl("No words match the search")
+        } else {
+            empty_lbl("You don't have any words")
+        };
+
+        let page: Element<_> = Column::new()
+            .spacing(10)
+            .push(head)
+            .push(
+                Scrollable::new(&mut self.scroll_entries)
+                    .spacing(5)
+                    .push(entries)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .padding(20)
+                    .align_items(Align::Center)
+                    .style(style),
+            )
+            .into();
+
+        self.toolbar.view(status, page)
+    }
+
+    /// The files of a folder import, before they become entries.
+    fn import_page(&mut self, status: Status) -> Element<Message> {
+        let style = status.style;
+        let candidates: &[Candidate] = self.import.as_deref().unwrap_or_default();
+        let ok = candidates.iter().filter(|c| c.error.is_none()).count();
+
+        let confirm = Button::new(
+            &mut self.confirm_import_btn,
+            Text::new(format!("Import {} Sounds", ok)),
+        )
+        .on_press(Message::ConfirmImport)
+        .padding(15)
+        .style(style);
+        let cancel = Button::new(&mut self.cancel_import_btn, Text::new("Cancel"))
+            .on_press(Message::CancelImport)
+            .padding(15)
+            .style(style);
 
         let head = Row::new()
             .spacing(20)
-            .push(Text::new("Word"))
-            .push(Text::new("Channel Id"))
-            .push(Text::new("Sound file"))
-            .padding(20);
+            .push(Text::new(format!(
+                "{} of {} files can be imported",
+                ok,
+                candidates.len()
+            )))
+            .push(Space::with_width(Length::Fill))
+            .push(confirm)
+            .push(cancel)
+            .align_items(Align::Center);
 
-        let black_list_head = Row::new()
-            .spacing(20)
-            .push(Text::new("Black list"))
-            .padding(20);
+        let files: Element<_> = if candidates.is_empty() {
+            empty_lbl("No sound files match the pattern")
+        } else {
+            candidates
+                .iter()
+                .fold(Column::new().spacing(10), |col, candidate| {
+                    let file = candidate
+                        .path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let mut row = Row::new()
+                        .spacing(20)
+                        .push(Text::new(&candidate.word).width(Length::Units(200)))
+                        .push(Text::new(file).width(Length::Units(300)));
+                    if let Some(error) = &candidate.error {
+                        row = row.push(Text::new(error).color(ERROR_COLOR));
+                    }
+                    col.push(row)
+                })
+                .into()
+        };
+
+        let page: Element<_> = Column::new()
+            .spacing(10)
+            .push(head)
+            .push(
+                Scrollable::new(&mut self.scroll_import)
+                    .push(files)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .padding(20)
+                    .style(style),
+            )
+            .into();
 
-        let entries: Element<_> = if self.entries.len() > 0 {
-            self.entries
+        self.toolbar.view(status, page)
+    }
+
+    fn blacklist_page(&mut self, status: Status) -> Element<Message> {
+        let style = status.style;
+
+        let add_black_word = Button::new(
+            &mut self.add_black_word_btn,
+            Text::new("Add Black Listed Word"),
+        )
+        .on_press(Message::AddBlackWord)
+        .padding(15)
+        .style(style);
+
+        let black_entries: Element<_> = if !self.blacklist_entries.is_empty() {
+            self.blacklist_entries
                 .iter_mut()
-                .enumerate()
-                .fold(Column::new().spacing(20), |col, (i, entry)| {
+                .fold(Column::new().spacing(20), |col, entry| {
+                    let id = entry.id;
                     col.push(
                         entry
-                            .view()
-                            .map(move |message| Message::EntryMessage(i, message)),
+                            .view(style)
+                            .map(move |msg| Message::BlackWordMessage(id, msg)),
                     )
                 })
                 .align_items(Align::Center)
                 .into()
         } else {
-            Text::new("You don't have any words")
-                .width(Length::Fill)
-                .size(25)
-                .horizontal_alignment(HorizontalAlignment::Center)
+            empty_lbl("You don't have any blacklisted words")
+        };
+
+        let page: Element<_> = Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .push(Text::new("Black list"))
+                    .push(Space::with_width(Length::Fill))
+                    .push(add_black_word)
+                    .align_items(Align::Center),
+            )
+            .push(
+                Scrollable::new(&mut self.scroll_black_entries)
+                    .spacing(5)
+                    .push(black_entries)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .padding(20)
+                    .align_items(Align::Center)
+                    .style(style),
+            )
+            .into();
+
+        self.toolbar.view(status, page)
+    }
+
+    fn settings_page(&mut self, status: Status) -> Element<Message> {
+        let style = status.style;
+
+        let bot_btn = Button::new(&mut self.start_bot_btn, Text::new("Start Bot"))
+            .on_press(Message::StartBotPressed)
+            .padding(15)
+            .style(style);
+
+        let token_input: Element<_> = if self.token_source.is_external() {
+            Text::new(self.token_source.label()).into()
+        } else {
+            Row::new()
+                .spacing(20)
+                .push(
+                    TextInput::new(
+                        &mut self.token,
+                        "Bot Token",
+                        &self.token_value,
+                        Message::TokenChanged,
+                    )
+                    .password()
+                    .padding(15)
+                    .style(style),
+                )
+                .push(Text::new(self.token_source.label()))
+                .align_items(Align::Center)
                 .into()
         };
 
-        let black_entries: Element<_> = if self.blacklist_entries.len() > 0 {
-            self.blacklist_entries
+        let quiet_hours_input = TextInput::new(
+            &mut self.quiet_hours,
+            "Quiet hours (e.g. mon-fri 22:00-07:00)",
+            &self.settings.quiet_hours,
+            Message::QuietHoursChanged,
+        )
+        .padding(15)
+        .style(style);
+
+        let timezone_input = TextInput::new(
+            &mut self.timezone,
+            "Timezone (e.g. Europe/Athens)",
+            &self.settings.timezone,
+            Message::TimezoneChanged,
+        )
+        .padding(15)
+        .style(style);
+
+        let history_limit_input = TextInput::new(
+            &mut self.history_limit,
+            "History size",
+            &self.history_limit_value,
+            Message::HistoryLimitChanged,
+        )
+        .padding(15)
+        .width(Length::Units(150))
+        .style(style);
+
+        let default_chn_id_input = TextInput::new(
+            &mut self.default_chn_id,
+            "Channel Id for entries made from dropped files",
+            &self.settings.default_chn_id,
+            Message::DefaultChnIdChanged,
+        )
+        .padding(15)
+        .style(style);
+
+        let current = &self.profile;
+        let profiles = self.profiles.iter().zip(self.profile_btns.iter_mut()).fold(
+            Row::new().spacing(10),
+            |row, (name, state)| {
+                let mut btn = Button::new(state, Text::new(name)).padding(15).style(style);
+                if current.as_ref() != Some(name) {
+                    btn = btn.on_press(Message::SwitchProfile(name.clone()));
+                }
+                row.push(btn)
+            },
+        );
+        let new_profile_input = TextInput::new(
+            &mut self.new_profile,
+            "New profile",
+            &self.new_profile_value,
+            Message::NewProfileChanged,
+        )
+        .on_submit(Message::CreateProfile)
+        .padding(15)
+        .width(Length::Units(200))
+        .style(style);
+        let create_profile = Button::new(&mut self.create_profile_btn, Text::new("Create"))
+            .on_press(Message::CreateProfile)
+            .padding(15)
+            .style(style);
+
+        let default_library = library::default_root();
+        let library_dir_input = TextInput::new(
+            &mut self.library_dir,
+            &default_library.to_string_lossy(),
+            &self.settings.library_dir,
+            Message::LibraryDirChanged,
+        )
+        .padding(15)
+        .style(style);
+        let relink_btn = Button::new(&mut self.relink_btn, Text::new("Relink Missing Files"))
+            .on_press(Message::RelinkFiles)
+            .padding(15)
+            .style(style);
+
+        let theme_btn = Button::new(&mut self.theme_btn, Text::new(self.settings.theme.label()))
+            .on_press(Message::NextTheme)
+            .padding(15)
+            .style(style);
+
+        let export_board = Button::new(&mut self.export_board_btn, Text::new("Export"))
+            .on_press(Message::ExportBoard)
+            .padding(15)
+            .style(style);
+        let export_token = Checkbox::new(
+            self.export_token,
+            "Include the token",
+            Message::ExportTokenToggled,
+        );
+        let import_board = Button::new(&mut self.import_board_btn, Text::new("Import"))
+            .on_press(Message::ImportBoard)
+            .padding(15)
+            .style(style);
+        let import_mode = Button::new(
+            &mut self.import_mode_btn,
+            Text::new(self.import_mode.label()),
+        )
+        .on_press(Message::NextImportMode)
+        .padding(15)
+        .style(style);
+
+        let export_pack = Button::new(&mut self.export_pack_btn, Text::new("Export Pack"))
+            .on_press(Message::ExportPack)
+            .padding(15)
+            .style(style);
+        let import_pack = Button::new(&mut self.import_pack_btn, Text::new("Import Pack"))
+            .on_press(Message::ImportPack)
+            .padding(15)
+            .style(style);
+
+        let add_policy = Button::new(&mut self.add_policy_btn, Text::new("Add Channel Policy"))
+            .on_press(Message::AddPolicy)
+            .padding(15)
+            .style(style);
+
+        let policies: Element<_> = if !self.policy_entries.is_empty() {
+            self.policy_entries
                 .iter_mut()
-                .enumerate()
-                .fold(Column::new().spacing(20), |col, (i, entry)| {
+                .fold(Column::new().spacing(20), |col, entry| {
+                    let id = entry.id;
                     col.push(
                         entry
-                            .view()
-                            .map(move |msg| Message::BlackWordMessage(i, msg)),
+                            .view(style)
+                            .map(move |msg| Message::PolicyMessage(id, msg)),
                     )
                 })
                 .align_items(Align::Center)
                 .into()
         } else {
-            Text::new("You don't have any blacklisted words")
-                .width(Length::Fill)
-                .size(25)
-                .horizontal_alignment(HorizontalAlignment::Center)
-                .into()
+            empty_lbl("All channels play every match")
         };
 
-        Container::new(
-            Column::new()
-                .padding(20)
-                .align_items(Align::Center)
-                .push(head)
-                .push(
-                    Scrollable::new(&mut self.scroll_entries)
-                        .spacing(5)
-                        .push(entries)
-                        .height(Length::Shrink)
-                        .width(Length::Fill)
-                        .max_height(200)
-                        .padding(20)
-                        .align_items(Align::Center),
-                )
-                .push(add_entry)
-                .push(Space::with_height(Length::Units(50)))
-                .push(black_list_head)
-                .push(
-                    Scrollable::new(&mut self.scroll_black_entries)
-                        .spacing(5)
-                        .push(black_entries)
-                        .height(Length::Shrink)
-                        .width(Length::Fill)
-                        .max_height(200)
-                        .padding(20)
-                        .align_items(Align::Center),
-                )
-                .push(add_black_word)
-                .push(
-                    Row::new()
-                        .padding(20)
-                        .push(bot_btn)
-                        .push(token_input)
-                        .align_items(Align::Center),
-                )
-                .push(save_btn)
-                .push(Space::with_height(Length::Fill))
-                .push(messages_lbl)
-                .align_items(Align::Center),
+        let settings = Column::new()
+            .spacing(20)
+            .push(setting(
+                "Bot",
+                Row::new().spacing(20).push(bot_btn).push(token_input),
+            ))
+            .push(setting("Quiet hours", quiet_hours_input))
+            .push(setting(
+                "Timezone",
+                with_error(
+                    timezone_input,
+                    schedule::parse_timezone(&self.settings.timezone).err(),
+                ),
+            ))
+            .push(setting(
+                "History size",
+                with_error(
+                    history_limit_input,
+                    settings::parse_history_limit(&self.history_limit_value).err(),
+                ),
+            ))
+            .push(setting("Dropped files' channel", default_chn_id_input))
+            .push(setting(
+                "Sound library",
+                Row::new()
+                    .spacing(20)
+                    .push(library_dir_input)
+                    .push(relink_btn)
+                    .align_items(Align::Center),
+            ))
+            .push(setting("Theme", theme_btn))
+            .push(setting(
+                "Output device",
+                Text::new(match &self.player {
+                    Some(player) => player.device_name(),
+                    None => String::from("No output device"),
+                }),
+            ))
+            .push(setting(
+                "Profile",
+                profiles
+                    .push(new_profile_input)
+                    .push(create_profile)
+                    .align_items(Align::Center),
+            ))
+            .push(setting("Database", Text::new(db::location())))
+            .push(setting(
+                "Board file",
+                Row::new()
+                    .spacing(20)
+                    .push(export_board)
+                    .push(export_token)
+                    .push(import_board)
+                    .push(import_mode)
+                    .align_items(Align::Center),
+            ))
+            .push(setting(
+                "Sound pack",
+                Row::new()
+                    .spacing(20)
+                    .push(export_pack)
+                    .push(import_pack)
+                    .push(Text::new("Packs are unpacked into the library"))
+                    .align_items(Align::Center),
+            ))
+            .push(
+                Row::new()
+                    .push(Text::new("Channel policies"))
+                    .push(Space::with_width(Length::Fill))
+                    .push(add_policy)
+                    .align_items(Align::Center),
+            )
+            .push(policies);
+
+        let page: Element<_> = Scrollable::new(&mut self.scroll_policies)
+            .push(settings)
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .padding(20)
+            .style(style)
+            .into();
+
+        self.toolbar.view(status, page)
+    }
+
+    fn log_page(&mut self, status: Status) -> Element<Message> {
+        let log_filter_kinds = &self.log_filter;
+        let log_filter = Kind::ALL.iter().fold(Row::new().spacing(20), |row, kind| {
+            let kind = *kind;
+            row.push(Checkbox::new(
+                log_filter_kinds.contains(&kind),
+                kind.label(),
+                move |shown| Message::LogFilterToggled(kind, shown),
+            ))
+        });
+
+        let log = self
+            .log
+            .iter()
+            .rev()
+            .filter(|event| log_filter_kinds.contains(&event.kind))
+            .fold(Column::new().spacing(5), |col, event| {
+                let line = Text::new(format!(
+                    "{} [{}] {}",
+                    event.time.format("%H:%M:%S"),
+                    event.kind.label(),
+                    event.text
+                ))
+                .size(16);
+                col.push(if event.kind == Kind::Error {
+                    line.color(ERROR_COLOR)
+                } else {
+                    line
+                })
+            });
+
+        let page: Element<_> = Column::new()
+            .spacing(10)
+            .push(log_filter)
+            .push(
+                Scrollable::new(&mut self.scroll_log)
+                    .push(log)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .padding(20)
+                    .style(self.style),
+            )
+            .into();
+
+        self.toolbar.view(status, page)
+    }
+
+    fn stats_page(&mut self, status: Status) -> Element<Message> {
+        let range = Button::new(
+            &mut self.stats_range_btn,
+            Text::new(self.stats_range.label()),
         )
-        .style(self.style)
+        .on_press(Message::NextStatsRange)
+        .padding(15)
+        .style(self.style);
+
+        let export = Button::new(&mut self.stats_export_btn, Text::new("Export CSV"))
+            .on_press(Message::ExportStats)
+            .padding(15)
+            .style(self.style);
+
+        let counts = Row::new()
+            .spacing(50)
+            .push(counts_column("Top triggers", &self.stats.triggers))
+            .push(counts_column("Top users", &self.stats.users))
+            .push(counts_column("Channels", &self.stats.channels))
+            .push(counts_column("Per hour", &self.stats.hours))
+            .push(counts_column("Per day", &self.stats.days));
+
+        let page: Element<_> = Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .push(range)
+                    .push(export)
+                    .align_items(Align::Center),
+            )
+            .push(
+                Scrollable::new(&mut self.scroll_stats)
+                    .push(counts)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .padding(20)
+                    .style(self.style),
+            )
+            .into();
+
+        self.toolbar.view(status, page)
+    }
+}
+
+/// A row of the settings page, with `label` in front of `control`.
+fn setting<'a>(label: &str, control: impl Into<Element<'a, Message>>) -> Row<'a, Message> {
+    Row::new()
+        .spacing(20)
+        .push(Text::new(label).width(Length::Units(150)))
+        .push(control)
+        .align_items(Align::Center)
+}
+
+/// `control` followed by `error`, when there is one.
+fn with_error<'a>(
+    control: impl Into<Element<'a, Message>>,
+    error: Option<String>,
+) -> Row<'a, Message> {
+    let row = Row::new()
+        .spacing(20)
+        .push(control)
+        .align_items(Align::Center);
+    match error {
+        Some(error) => row.push(Text::new(error).color(ERROR_COLOR)),
+        None => row,
+    }
+}
+
+fn empty_lbl<'a>(text: &str) -> Element<'a, Message> {
+    Text::new(text)
+        .width(Length::Fill)
+        .size(25)
+        .horizontal_alignment(HorizontalAlignment::Center)
         .into()
+}
+
+fn counts_column<'a>(title: &str, counts: &[Count]) -> Column<'a, Message> {
+    let column = Column::new().spacing(5).push(Text::new(title).size(25));
+    if counts.is_empty() {
+        return column.push(Text::new("No plays"));
     }
+
+    counts.iter().fold(column, |col, count| {
+        col.push(Text::new(format!("{}: {}", count.name, count.plays)))
+    })
 }
 
-async fn start_bot(
-    token: String,
-    words: Arc<Mutex<Vec<Word>>>,
-    black_list: Vec<String>,
-    msg: Arc<Mutex<String>>,
-) {
-    bot::start(token, words, black_list, msg).await;
+/// Open the database `db` points to, creating it when it is new.
+async fn open_db() -> SqlitePool {
+    let pool = db::get_pool().await;
+    db::create_tables(&pool).await;
+    pool
+}
+
+async fn start_bot(token: String, config: bot::Config, events: activity::Sender) {
+    bot::start(token, config, events).await;
 }
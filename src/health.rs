@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::player::{self, FileInfo};
+
+use tokio::task;
+
+/// The files that can't be played and why, by `Word.path`.
+/// Shared with the bot, which skips them.
+pub type Broken = Arc<Mutex<HashMap<String, String>>>;
+
+/// Open every file in `paths` and decode its header.
+pub async fn check(paths: Vec<String>) -> Vec<(String, Result<FileInfo, String>)> {
+    task::block_in_place(|| {
+        paths
+            .into_iter()
+            .map(|path| {
+                let info = player::file_info(&path);
+                (path, info)
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,54 @@
+/// What was decided for a matched trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Played,
+    Blacklisted,
+    /// Quiet hours or outside of the trigger's schedule.
+    Scheduled,
+    /// Lost the probability roll.
+    Chance,
+    /// Another trigger was picked by the channel policy.
+    NotChosen,
+    Error,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Played => "played",
+            Outcome::Blacklisted => "blacklisted",
+            Outcome::Scheduled => "scheduled",
+            Outcome::Chance => "chance",
+            Outcome::NotChosen => "not_chosen",
+            Outcome::Error => "error",
+        }
+    }
+}
+
+impl From<&str> for Outcome {
+    fn from(s: &str) -> Self {
+        match s {
+            "played" => Outcome::Played,
+            "blacklisted" => Outcome::Blacklisted,
+            "scheduled" => Outcome::Scheduled,
+            "chance" => Outcome::Chance,
+            "not_chosen" => Outcome::NotChosen,
+            _ => Outcome::Error,
+        }
+    }
+}
+
+/// A row of the `play_history` table.
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Unix timestamp in seconds.
+    pub played_at: i64,
+    pub guild_id: String,
+    pub chn_id: String,
+    pub author_id: String,
+    pub author: String,
+    /// The matched trigger, or the blacklisted word.
+    pub word: String,
+    pub path: String,
+    pub outcome: Outcome,
+}
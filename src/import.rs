@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::player;
+use super::word::keyword_from_file;
+
+use tokio::task;
+
+/// The file extensions that are imported, the formats rodio can decode.
+pub const EXTENSIONS: [&str; 4] = ["mp3", "wav", "ogg", "flac"];
+
+/// A file found by `scan`, to be shown before importing it.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub word: String,
+    /// Why the file won't be imported.
+    pub error: Option<String>,
+}
+
+/// Determine if `name` matches `pattern`, where `*` is any text and `?` any character.
+/// Case is ignored and an empty pattern matches everything.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    pattern.is_empty() || matches(&pattern, &name)
+}
+
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Ask for a folder and find the sound files in it whose names match `pattern`.
+/// Returns `None` when the user cancelled.
+pub async fn scan(pattern: String) -> Result<Option<Vec<Candidate>>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_pick_folder(None).expect("Error opening nfd");
+        let dir = match res {
+            nfd::Response::Okay(dir) => dir,
+            _ => return Ok(None),
+        };
+
+        let mut candidates = vec![];
+        let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir, e))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read {}: {}", dir, e))?
+                .path();
+            let is_sound = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()));
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if !path.is_file() || !is_sound || !matches_pattern(&pattern, &name) {
+                continue;
+            }
+
+            candidates.push(Candidate {
+                word: keyword_from_file(&path),
+                error: player::check_file(&path.to_string_lossy()).err(),
+                path,
+            });
+        }
+
+        candidates.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Some(candidates))
+    })
+}
@@ -0,0 +1,251 @@
+//! The sound library, a folder that chosen sound files are copied into.
+//! Paths of files in it are stored relative to it, so the board keeps working
+//! when the library is moved, as long as the setting points to its new place.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+use super::db;
+use super::import::EXTENSIONS;
+use super::word::{Source, Word};
+
+use tokio::task;
+
+/// `BoardSettings::library_dir`, shared with the bot.
+static ROOT: RwLock<String> = RwLock::new(String::new());
+
+/// The library used when none is set, next to the database.
+pub fn default_root() -> PathBuf {
+    Path::new(&db::location())
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("sounds")
+}
+
+/// Use `dir` as the library, the default one when it is empty.
+pub fn set_root(dir: &str) {
+    *ROOT.write().unwrap() = dir.trim().to_string();
+}
+
+pub fn root() -> PathBuf {
+    let dir = ROOT.read().unwrap();
+    if dir.is_empty() {
+        default_root()
+    } else {
+        PathBuf::from(dir.as_str())
+    }
+}
+
+/// Where the file of a stored `path` is.
+pub fn resolve(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root().join(path)
+    }
+}
+
+/// The name of the file at `path`, or `path` itself when it has none.
+pub fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Copy the file at `path` into the library, unless it already is there.
+/// Returns the path to store and the hash of the file.
+pub fn add_file(path: &Path) -> Result<(String, String), String> {
+    let hash = hash_file(path)?;
+    let root = root();
+    fs::create_dir_all(&root).map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let path =
+        fs::canonicalize(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    if let Ok(relative) = path.strip_prefix(&root) {
+        return Ok((to_stored(relative), hash));
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("sound"));
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    // A different file with the same name gets a number, the same file is reused
+    let mut name = format!("{}{}", stem, extension);
+    let mut n = 1;
+    while root.join(&name).exists() && hash_file(&root.join(&name))? != hash {
+        n += 1;
+        name = format!("{}_{}{}", stem, n, extension);
+    }
+
+    let target = root.join(&name);
+    if !target.exists() {
+        fs::copy(&path, &target).map_err(|e| {
+            format!(
+                "Failed to copy {} to {}: {}",
+                path.display(),
+                target.display(),
+                e
+            )
+        })?;
+    }
+    Ok((name, hash))
+}
+
+/// What `add_file` returns.
+pub type Added = Result<(String, String), String>;
+
+/// `add_file` without blocking the GUI.
+pub async fn add(path: PathBuf) -> Added {
+    task::block_in_place(|| add_file(&path))
+}
+
+/// `add_file` for each of `paths`, in order.
+pub async fn add_all(paths: Vec<PathBuf>) -> Vec<(PathBuf, Added)> {
+    task::block_in_place(|| {
+        paths
+            .into_iter()
+            .map(|path| {
+                let added = add_file(&path);
+                (path, added)
+            })
+            .collect()
+    })
+}
+
+/// A path relative to the library, with `/` so that it works on every system.
+fn to_stored(relative: &Path) -> String {
+    relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// FNV-1a of the file's content, enough to tell sound files apart.
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let read = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        for byte in buf[..read].iter() {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    Ok(format!("{:016x}", hash))
+}
+
+/// What `relink` did.
+#[derive(Debug, Clone, Default)]
+pub struct Relink {
+    /// The entries that were found, with their new path and hash.
+    pub found: Vec<(i64, String, String)>,
+    /// The words that are still missing.
+    pub missing: Vec<String>,
+}
+
+/// Ask for a folder and search it for the missing files of `words`.
+/// A file is found when it has the same name and, if known, the same hash,
+/// or when it was renamed but has the same hash. Found files are added to the library.
+/// Returns `None` when the user cancelled.
+pub async fn relink(words: Vec<(i64, Word)>) -> Result<Option<Relink>, String> {
+    task::block_in_place(|| {
+        let missing: Vec<(i64, Word)> = words
+            .into_iter()
+            .filter(|(_, w)| w.source == Source::File && !resolve(&w.path).is_file())
+            .collect();
+        if missing.is_empty() {
+            return Ok(Some(Relink::default()));
+        }
+
+        let res = nfd::open_pick_folder(None).expect("Error opening nfd");
+        let dir = match res {
+            nfd::Response::Okay(dir) => PathBuf::from(dir),
+            _ => return Ok(None),
+        };
+
+        let mut files = vec![];
+        find_sounds(&dir, &mut files);
+        // Hashing is slow, so every file is hashed at most once
+        let mut hashes: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let mut hash_of = |path: &PathBuf| {
+            hashes
+                .entry(path.clone())
+                .or_insert_with(|| hash_file(path).ok())
+                .clone()
+        };
+
+        let mut relink = Relink::default();
+        for (id, word) in missing {
+            let name = file_name(&word.path);
+            let same_name: Vec<&PathBuf> = files
+                .iter()
+                .filter(|f| f.file_name().is_some_and(|n| n.to_string_lossy() == name))
+                .collect();
+
+            let found = if word.hash.is_empty() {
+                // Without a hash only an unambiguous name can be trusted
+                if same_name.len() == 1 {
+                    Some(same_name[0].clone())
+                } else {
+                    None
+                }
+            } else {
+                let hash = Some(word.hash.clone());
+                same_name
+                    .iter()
+                    .find(|f| hash_of(f) == hash)
+                    .map(|f| (*f).clone())
+                    .or_else(|| files.iter().find(|f| hash_of(f) == hash).cloned())
+            };
+
+            match found.map(|path| add_file(&path)) {
+                Some(Ok((path, hash))) => relink.found.push((id, path, hash)),
+                Some(Err(e)) => relink.missing.push(format!("{} ({})", word.word, e)),
+                None => relink.missing.push(word.word),
+            }
+        }
+
+        Ok(Some(relink))
+    })
+}
+
+/// Add the sound files in `dir` and its subfolders to `files`.
+fn find_sounds(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_sounds(&path, files);
+        } else if path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()))
+        {
+            files.push(path);
+        }
+    }
+}
@@ -1,20 +1,67 @@
 #![windows_subsystem = "windows"]
 
+mod activity;
 mod black_word;
+mod blacklist;
+mod board_file;
 mod bot;
+mod condition;
 mod db;
+mod dropped;
 mod entry;
+mod filter;
 mod gui;
+mod health;
+mod history;
+mod import;
+mod library;
+mod match_mode;
+mod player;
+mod policy;
+mod policy_entry;
+mod profile;
+mod schedule;
 mod schema;
+mod settings;
+mod sound_pack;
+mod stats;
 mod style;
+mod token;
+mod tts;
+mod undo;
 mod word;
 
+use std::env;
+use std::process;
 use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use tokio::runtime::Runtime;
 
 fn main() {
+    let mut db_flag = None;
+    let mut args = vec![];
+    let mut all_args = env::args().skip(1);
+    while let Some(arg) = all_args.next() {
+        if arg == "--db" {
+            db_flag = Some(all_args.next().unwrap_or_else(|| {
+                eprintln!("--db needs the path of a database file");
+                process::exit(1);
+            }));
+        } else {
+            args.push(arg);
+        }
+    }
+    let profile = profile::open_startup(db_flag);
+
+    if let [command, path] = args.as_slice() {
+        if command == "export-pack" {
+            export_pack(path);
+            return;
+        }
+    }
+
     let (sx1, rx1) = channel();
     let (sx2, rx2) = channel();
     let (sx3, rx3) = channel();
@@ -38,5 +85,35 @@ fn main() {
     let words = rx2.recv().unwrap();
     let blackwords = rx3.recv().unwrap();
 
-    gui::main(pool, words, blackwords);
+    gui::main(pool, words, blackwords, profile);
+}
+
+/// Write the saved triggers to a sound pack at `path`, without opening the window.
+fn export_pack(path: &str) {
+    let mut rt = Runtime::new().unwrap();
+    let words = rt.block_on(async {
+        let pool = db::get_pool().await;
+        db::create_tables(&pool).await;
+        let words = db::get_words(&pool).await;
+        let settings = db::get_settings(Arc::new(Mutex::new(pool))).await;
+        library::set_root(&settings.library_dir);
+        words
+    });
+
+    match sound_pack::write(path, &words) {
+        Ok(skipped) => {
+            for word in skipped.iter() {
+                eprintln!("Left out: {}", word);
+            }
+            println!(
+                "Exported {} triggers to {}",
+                words.len() - skipped.len(),
+                path
+            );
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
 }
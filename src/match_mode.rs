@@ -0,0 +1,111 @@
+use regex::{Regex, RegexBuilder};
+
+/// Where something was found in a message, as a byte range of its lowercase text.
+pub type Span = (usize, usize);
+
+/// How a keyword is looked for in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Anywhere in the message, "ass" matches "class".
+    Substring,
+    /// Only surrounded by non alphanumeric characters, "ass" doesn't match "class".
+    WholeWord,
+    /// The keyword is a case insensitive regular expression.
+    Regex,
+}
+
+impl MatchMode {
+    pub const ALL: [MatchMode; 3] = [MatchMode::Substring, MatchMode::WholeWord, MatchMode::Regex];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchMode::Substring => "substring",
+            MatchMode::WholeWord => "whole_word",
+            MatchMode::Regex => "regex",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|m| *m == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MatchMode::Substring => "Substring",
+            MatchMode::WholeWord => "Whole word",
+            MatchMode::Regex => "Regex",
+        }
+    }
+
+    /// Check that `pattern` can be used with this mode.
+    pub fn validate(self, pattern: &str) -> Result<(), String> {
+        if pattern.is_empty() {
+            return Err("The word can't be empty".to_string());
+        }
+        if self == MatchMode::Regex {
+            RegexBuilder::new(pattern)
+                .build()
+                .map_err(|e| format!("Invalid regex: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// A keyword ready to be looked for, a regex is compiled once when it is made.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    mode: MatchMode,
+    keyword: String,
+    /// `None` for the other modes, or when the regex is invalid, which then never matches.
+    regex: Option<Regex>,
+}
+
+impl Pattern {
+    pub fn new(mode: MatchMode, keyword: &str) -> Self {
+        let regex = match mode {
+            MatchMode::Regex => RegexBuilder::new(keyword)
+                .case_insensitive(true)
+                .build()
+                .ok(),
+            _ => None,
+        };
+        Self {
+            mode,
+            keyword: keyword.to_string(),
+            regex,
+        }
+    }
+
+    /// Find the first match in the already lowercase `text`.
+    pub fn find(&self, text: &str) -> Option<Span> {
+        let keyword = &self.keyword;
+        match self.mode {
+            MatchMode::Substring => text.find(keyword).map(|i| (i, i + keyword.len())),
+            MatchMode::WholeWord => text
+                .match_indices(keyword)
+                .map(|(i, _)| (i, i + keyword.len()))
+                .find(|&(start, end)| {
+                    let before = text[..start].chars().next_back();
+                    let after = text[end..].chars().next();
+                    !matches!(before, Some(c) if c.is_alphanumeric())
+                        && !matches!(after, Some(c) if c.is_alphanumeric())
+                }),
+            MatchMode::Regex => self
+                .regex
+                .as_ref()?
+                .find(text)
+                .map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+impl From<&str> for MatchMode {
+    fn from(s: &str) -> Self {
+        match s {
+            "whole_word" => MatchMode::WholeWord,
+            "regex" => MatchMode::Regex,
+            _ => MatchMode::Substring,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::library;
+use super::tts;
+use super::word::{Source, Word};
+
+use rodio::{Decoder, Device, DeviceTrait, Sink, Source as _};
+use tokio::task;
+
+/// Plays sounds on the default output device.
+/// The bot and the editor's previews share one, so "Stop all" stops both.
+pub struct Player {
+    device: Device,
+    sink: Mutex<Sink>,
+}
+
+impl Player {
+    pub fn new() -> Result<Self, String> {
+        let device =
+            rodio::default_output_device().ok_or_else(|| String::from("No audio output device"))?;
+        let sink = Sink::new(&device);
+        Ok(Self {
+            device,
+            sink: Mutex::new(sink),
+        })
+    }
+
+    pub fn device_name(&self) -> String {
+        self.device
+            .name()
+            .unwrap_or_else(|_| String::from("Unknown device"))
+    }
+
+    pub fn play_file(&self, path: &str) -> Result<(), String> {
+        let source = decode(path)?;
+
+        self.sink.lock().unwrap().append(source);
+        Ok(())
+    }
+
+    /// Play a wav file, as made by `tts::synthesize`.
+    pub fn play_wav(&self, wav: Vec<u8>) -> Result<(), String> {
+        let source = Decoder::new(Cursor::new(wav))
+            .map_err(|e| format!("Failed to decode speech: {}", e))?;
+
+        self.sink.lock().unwrap().append(source);
+        Ok(())
+    }
+
+    /// Stop what is playing and drop everything that is queued.
+    pub fn stop_all(&self) {
+        // A stopped sink stays stopped, so it is replaced
+        let mut sink = self.sink.lock().unwrap();
+        *sink = Sink::new(&self.device);
+    }
+}
+
+fn decode(path: &str) -> Result<Decoder<BufReader<File>>, String> {
+    let path = library::resolve(path);
+    if !path.is_file() {
+        return Err(format!("{} doesn't exist", path.display()));
+    }
+    let file =
+        File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    Decoder::new(BufReader::new(file))
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+}
+
+/// Make sure `path` is a sound file that can be played.
+pub fn check_file(path: &str) -> Result<(), String> {
+    decode(path).map(|_| ())
+}
+
+/// `check_file` for commands, the file is decoded off the GUI's thread.
+pub async fn check(path: String) -> Result<(), String> {
+    task::block_in_place(|| check_file(&path))
+}
+
+/// What the header of a sound file says.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    /// Not every format knows it without decoding the whole file.
+    pub duration: Option<Duration>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl FileInfo {
+    /// e.g. `0:03, 44100 Hz, stereo`
+    pub fn summary(&self) -> String {
+        let channels = match self.channels {
+            1 => String::from("mono"),
+            2 => String::from("stereo"),
+            n => format!("{} channels", n),
+        };
+        match self.duration {
+            Some(duration) => {
+                let secs = duration.as_secs();
+                format!(
+                    "{}:{:02}, {} Hz, {}",
+                    secs / 60,
+                    secs % 60,
+                    self.sample_rate,
+                    channels
+                )
+            }
+            None => format!("{} Hz, {}", self.sample_rate, channels),
+        }
+    }
+}
+
+pub fn file_info(path: &str) -> Result<FileInfo, String> {
+    let decoder = decode(path)?;
+    Ok(FileInfo {
+        duration: decoder.total_duration(),
+        sample_rate: decoder.sample_rate(),
+        channels: decoder.channels(),
+    })
+}
+
+/// Play what `word` would play, TTS templates are filled with example values.
+pub async fn preview(player: Arc<Player>, word: Word) -> Result<(), String> {
+    match word.source {
+        Source::File => player.play_file(&word.path),
+        Source::Tts => {
+            let speech = tts::render(
+                &word.tts_template,
+                &[
+                    ("author", "someone"),
+                    ("message", &word.word),
+                    ("word", &word.word),
+                ],
+            );
+            let wav = task::block_in_place(|| tts::synthesize(&speech, tts::TIMEOUT))?;
+            player.play_wav(wav)
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use super::match_mode::Span;
+use super::word::{self, Word};
+
+/// Which of the triggers matched by a single message get played.
+/// It picks among the triggers that passed their probability roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    All,
+    HighestPriority,
+    FirstInMessage,
+    Longest,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 4] = [
+        Policy::All,
+        Policy::HighestPriority,
+        Policy::FirstInMessage,
+        Policy::Longest,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Policy::All => "all",
+            Policy::HighestPriority => "highest_priority",
+            Policy::FirstInMessage => "first_in_message",
+            Policy::Longest => "longest",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|p| *p == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Policy::All => "Play all matches",
+            Policy::HighestPriority => "Highest priority",
+            Policy::FirstInMessage => "First in message",
+            Policy::Longest => "Longest keyword",
+        }
+    }
+
+    /// Pick the words to play out of the ones `matched` in a message, with where they matched.
+    /// Ties go to the word that comes first in the message.
+    pub fn resolve(self, mut matched: Vec<(&Word, Span)>) -> Vec<&Word> {
+        if self == Policy::All || matched.len() < 2 {
+            return matched.into_iter().map(|(word, _)| word).collect();
+        }
+
+        matched.sort_by_key(|(_, span)| *span);
+        let matched: Vec<&Word> = matched.into_iter().map(|(word, _)| word).collect();
+
+        let winner = match self {
+            Policy::HighestPriority => matched
+                .iter()
+                .rev()
+                .max_by_key(|word| word.priority)
+                .copied(),
+            Policy::Longest => matched
+                .iter()
+                .rev()
+                .max_by_key(|word| word.word.chars().count())
+                .copied(),
+            _ => matched.first().copied(),
+        };
+
+        winner.into_iter().collect()
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::All
+    }
+}
+
+impl From<&str> for Policy {
+    fn from(s: &str) -> Self {
+        match s {
+            "highest_priority" => Policy::HighestPriority,
+            "first_in_message" => Policy::FirstInMessage,
+            "longest" => Policy::Longest,
+            _ => Policy::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChannelPolicy {
+    pub chn_id: String,
+    pub policy: Policy,
+}
+
+impl ChannelPolicy {
+    pub fn is_valid(&self) -> bool {
+        word::is_snowflake(&self.chn_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(word: &str, priority: i32) -> Word {
+        Word {
+            word: word.to_string(),
+            priority,
+            ..Word::default()
+        }
+    }
+
+    fn resolve(policy: Policy, matched: &[(&Word, Span)]) -> Vec<String> {
+        policy
+            .resolve(matched.to_vec())
+            .into_iter()
+            .map(|word| word.word.clone())
+            .collect()
+    }
+
+    #[test]
+    fn all_plays_everything() {
+        let (a, b) = (trigger("a", 0), trigger("b", 0));
+        assert_eq!(
+            resolve(Policy::All, &[(&b, (2, 3)), (&a, (0, 1))]),
+            ["b", "a"]
+        );
+    }
+
+    #[test]
+    fn highest_priority() {
+        let (low, high) = (trigger("low", 1), trigger("high", 5));
+        assert_eq!(
+            resolve(Policy::HighestPriority, &[(&low, (0, 3)), (&high, (4, 8))]),
+            ["high"]
+        );
+
+        // Ties go to the first in the message
+        let other = trigger("other", 5);
+        assert_eq!(
+            resolve(
+                Policy::HighestPriority,
+                &[(&high, (6, 10)), (&other, (0, 5))]
+            ),
+            ["other"]
+        );
+    }
+
+    #[test]
+    fn first_in_message_uses_where_they_matched() {
+        // A condition named "zzz" that matched at the start, before "horn"
+        let (horn, condition) = (trigger("horn", 0), trigger("zzz", 0));
+        assert_eq!(
+            resolve(
+                Policy::FirstInMessage,
+                &[(&horn, (3, 7)), (&condition, (0, 2))]
+            ),
+            ["zzz"]
+        );
+    }
+
+    #[test]
+    fn longest() {
+        let (short, long) = (trigger("air", 0), trigger("air horn", 0));
+        assert_eq!(
+            resolve(Policy::Longest, &[(&short, (0, 3)), (&long, (0, 8))]),
+            ["air horn"]
+        );
+
+        // Ties go to the first in the message
+        let other = trigger("fog horn", 0);
+        assert_eq!(
+            resolve(Policy::Longest, &[(&long, (9, 17)), (&other, (0, 8))]),
+            ["fog horn"]
+        );
+    }
+
+    #[test]
+    fn one_match_is_always_played() {
+        let low = trigger("low", 0);
+        for policy in Policy::ALL.iter() {
+            assert_eq!(resolve(*policy, &[(&low, (0, 3))]), ["low"]);
+        }
+        assert!(resolve(Policy::Longest, &[]).is_empty());
+    }
+
+    #[test]
+    fn channel_must_be_a_discord_id() {
+        let policy = |chn_id: &str| ChannelPolicy {
+            chn_id: chn_id.to_string(),
+            policy: Policy::All,
+        };
+        assert!(policy("123456789012345678").is_valid());
+        assert!(!policy("").is_valid());
+        assert!(!policy("12").is_valid());
+        assert!(!policy("channel").is_valid());
+    }
+}
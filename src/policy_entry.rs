@@ -0,0 +1,168 @@
+use super::policy::ChannelPolicy;
+use super::style::{Theme, ERROR_COLOR};
+
+use iced::{button, text_input, Align, Button, Command, Element, Length, Row, Text, TextInput};
+
+#[derive(Debug, Clone)]
+pub struct PolicyEntry {
+    /// The rowid of the entry in `channels`, it stays the same when saving.
+    pub id: i64,
+
+    pub policy: ChannelPolicy,
+    error: Option<String>,
+
+    state: PolicyState,
+}
+
+#[derive(Debug, Clone)]
+enum PolicyState {
+    Idle {
+        edit_btn: button::State,
+    },
+    Editing {
+        chn_id_in: text_input::State,
+        policy_btn: button::State,
+        done_btn: button::State,
+        delete_btn: button::State,
+    },
+}
+
+impl Default for PolicyState {
+    fn default() -> Self {
+        Self::Editing {
+            chn_id_in: text_input::State::new(),
+            policy_btn: button::State::new(),
+            done_btn: button::State::new(),
+            delete_btn: button::State::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PolicyMessage {
+    ChnIdChanged(String),
+    NextPolicy,
+    Edit,
+    DoneEditing,
+    Delete,
+}
+
+impl PolicyEntry {
+    pub fn new(id: i64) -> Self {
+        Self {
+            id,
+            policy: ChannelPolicy::default(),
+            error: None,
+            state: PolicyState::default(),
+        }
+    }
+
+    pub fn new_idle(id: i64) -> Self {
+        Self {
+            id,
+            policy: ChannelPolicy::default(),
+            error: None,
+            state: PolicyState::Idle {
+                edit_btn: button::State::new(),
+            },
+        }
+    }
+
+    pub fn update(&mut self, message: PolicyMessage) -> Command<PolicyMessage> {
+        match message {
+            // These are taken care of in gui.rs
+            PolicyMessage::Delete | PolicyMessage::DoneEditing => {}
+            PolicyMessage::ChnIdChanged(new) => self.policy.chn_id = new,
+            PolicyMessage::NextPolicy => self.policy.policy = self.policy.policy.next(),
+            PolicyMessage::Edit => self.state = PolicyState::default(),
+        }
+        Command::none()
+    }
+
+    /// Determine if Done found something wrong that wasn't fixed yet.
+    pub fn has_errors(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Validate the policy and stop editing it when it is valid,
+    /// `duplicate` is whether another policy is for the same channel.
+    pub fn done_editing(&mut self, duplicate: bool) {
+        self.error = if !self.policy.is_valid() {
+            Some("Not a Discord channel id".to_string())
+        } else if duplicate {
+            Some("There is already a policy for this channel".to_string())
+        } else {
+            None
+        };
+
+        if self.error.is_none() {
+            self.state = PolicyState::Idle {
+                edit_btn: button::State::new(),
+            }
+        }
+    }
+
+    pub fn view(&mut self, style: Theme) -> Element<PolicyMessage> {
+        match &mut self.state {
+            PolicyState::Idle { edit_btn } => {
+                let chn_id_lbl = Text::new(&self.policy.chn_id);
+                let policy_lbl = Text::new(self.policy.policy.label());
+                let edit_btn = Button::new(edit_btn, Text::new("edit"))
+                    .on_press(PolicyMessage::Edit)
+                    .padding(10)
+                    .style(style);
+
+                Row::new()
+                    .spacing(20)
+                    .push(chn_id_lbl)
+                    .push(policy_lbl)
+                    .push(edit_btn)
+                    .align_items(Align::Center)
+                    .into()
+            }
+            PolicyState::Editing {
+                chn_id_in,
+                policy_btn,
+                done_btn,
+                delete_btn,
+            } => {
+                let chn_id = TextInput::new(
+                    chn_id_in,
+                    "Channel Id",
+                    &self.policy.chn_id,
+                    PolicyMessage::ChnIdChanged,
+                )
+                .padding(20)
+                .width(Length::Fill)
+                .style(style);
+
+                let policy = Button::new(policy_btn, Text::new(self.policy.policy.label()))
+                    .on_press(PolicyMessage::NextPolicy)
+                    .padding(10)
+                    .style(style);
+
+                let done = Button::new(done_btn, Text::new("Done"))
+                    .on_press(PolicyMessage::DoneEditing)
+                    .padding(10)
+                    .style(style);
+                let delete = Button::new(delete_btn, Text::new("Delete"))
+                    .on_press(PolicyMessage::Delete)
+                    .padding(10)
+                    .style(style);
+
+                let mut row = Row::new()
+                    .spacing(20)
+                    .push(chn_id)
+                    .push(policy)
+                    .push(done)
+                    .push(delete);
+
+                if let Some(error) = &self.error {
+                    row = row.push(Text::new(error).color(ERROR_COLOR));
+                }
+
+                row.align_items(Align::Center).into()
+            }
+        }
+    }
+}
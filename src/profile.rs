@@ -0,0 +1,91 @@
+//! Profiles, named boards that each have their own database in the data directory.
+//! `--db <file>` or `SOUND_BOARD_DB` open a database file instead of a profile.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::db;
+
+pub const DEFAULT: &str = "default";
+
+/// Overrides the profile with a database file, like `--db`.
+pub const DB_VAR: &str = "SOUND_BOARD_DB";
+
+/// Where the profiles and the default sound library are,
+/// e.g. `~/.local/share/sound_board` on Linux.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sound_board")
+}
+
+pub fn path(profile: &str) -> PathBuf {
+    data_dir().join(format!("{}.db", profile))
+}
+
+/// The profile names that have a database, in order.
+pub fn list() -> Vec<String> {
+    let mut profiles: Vec<String> = fs::read_dir(data_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "db"))
+                .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !profiles.iter().any(|p| p == DEFAULT) {
+        profiles.push(DEFAULT.to_string());
+    }
+    profiles.sort();
+    profiles
+}
+
+/// Names are used as file names, so only letters, digits, `-` and `_` are allowed.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err("The profile name can't be empty".to_string())
+    } else if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        Err("Profile names can only have letters, digits, - and _".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Point `db` to the database of `profile` and remember it for the next start.
+pub fn open(profile: &str) {
+    let dir = data_dir();
+    fs::create_dir_all(&dir).expect("Failed to create the data directory");
+    // The file is only a convenience, the profile is still opened without it
+    let _ = fs::write(dir.join("profile"), profile);
+    db::set_location(&path(profile));
+}
+
+/// Point `db` to the database to start with: the `--db` file, then `SOUND_BOARD_DB`,
+/// then the profile used last. Returns the profile, `None` when a file is used.
+pub fn open_startup(db_flag: Option<String>) -> Option<String> {
+    if let Some(file) = db_flag.or_else(|| env::var(DB_VAR).ok()) {
+        db::set_location(Path::new(&file));
+        return None;
+    }
+
+    let last = fs::read_to_string(data_dir().join("profile"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| validate_name(name).is_ok())
+        .unwrap_or_else(|| DEFAULT.to_string());
+    let dir = data_dir();
+    fs::create_dir_all(&dir).expect("Failed to create the data directory");
+    // Boards used to be kept in `app.db` where the program was started
+    let old = Path::new("app.db");
+    if last == DEFAULT && !path(DEFAULT).exists() && old.is_file() {
+        let _ = fs::copy(old, path(DEFAULT));
+    }
+    open(&last);
+    Some(last)
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+static DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// A time range on some days of the week.
+/// When `start` is after `end` the window goes past midnight,
+/// and the days are the ones it starts on.
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    days: [bool; 7],
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    /// Parse a window like `mon-fri,sun 22:00-07:30`, the days are optional.
+    fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = s.split_whitespace();
+        let (days, times) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(times), None, None) => ([true; 7], times),
+            (Some(days), Some(times), None) => (parse_days(days)?, times),
+            _ => return Err(format!("Expected \"[days] HH:MM-HH:MM\", got \"{}\"", s)),
+        };
+
+        let mut times = times.splitn(2, '-');
+        let start = parse_time(times.next().unwrap_or_default())?;
+        let end = match times.next() {
+            Some(end) => parse_time(end)?,
+            None => return Err(format!("Missing end time in \"{}\"", s)),
+        };
+
+        Ok(Self { days, start, end })
+    }
+
+    fn contains(&self, weekday: usize, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.days[weekday] && self.start <= time && time < self.end
+        } else {
+            // Past midnight, the early part belongs to the day before
+            (self.days[weekday] && time >= self.start)
+                || (self.days[(weekday + 6) % 7] && time < self.end)
+        }
+    }
+}
+
+fn parse_days(s: &str) -> Result<[bool; 7], String> {
+    let day = |d: &str| {
+        DAYS.iter()
+            .position(|day| *day == d)
+            .ok_or_else(|| format!("Unknown day \"{}\", use one of {}", d, DAYS.join(", ")))
+    };
+
+    let mut days = [false; 7];
+    for part in s.to_lowercase().split(',') {
+        let mut range = part.splitn(2, '-');
+        let first = day(range.next().unwrap_or_default())?;
+        let last = match range.next() {
+            Some(last) => day(last)?,
+            None => first,
+        };
+
+        // Ranges can wrap around the week, e.g. `sat-mon`
+        let mut i = first;
+        loop {
+            days[i] = true;
+            if i == last {
+                break;
+            }
+            i = (i + 1) % 7;
+        }
+    }
+
+    Ok(days)
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| format!("Invalid time \"{}\", use HH:MM", s))
+}
+
+/// A list of time windows separated by `;`, e.g. `mon-fri 09:00-17:00; sat 10:00-12:00`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let windows = s
+            .split(';')
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(Window::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { windows })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Determine if `now` falls into one of the windows.
+    pub fn contains<T: TimeZone>(&self, now: &DateTime<T>) -> bool {
+        let weekday = now.weekday().num_days_from_monday() as usize;
+        let time = now.time();
+
+        self.windows.iter().any(|w| w.contains(weekday, time))
+    }
+}
+
+/// Parse a timezone name like `Europe/Athens`.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse().map_err(|_| {
+        format!(
+            "Unknown timezone \"{}\", use a name like Europe/Athens",
+            name
+        )
+    })
+}
+
+/// The current time in `tz`.
+pub fn now(tz: Tz) -> DateTime<Tz> {
+    Utc::now().with_timezone(&tz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    /// 2021-03-01 is a Monday, so `day` 0 is Monday.
+    fn at(day: u32, h: u32, m: u32) -> DateTime<Utc> {
+        let date = NaiveDate::from_ymd_opt(2021, 3, 1 + day).unwrap();
+        Utc.from_utc_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+    }
+
+    fn contains(schedule: &str, day: u32, h: u32, m: u32) -> bool {
+        Schedule::parse(schedule).unwrap().contains(&at(day, h, m))
+    }
+
+    #[test]
+    fn window_without_days_is_every_day() {
+        for day in 0..7 {
+            assert!(contains("09:00-17:00", day, 9, 0));
+            assert!(!contains("09:00-17:00", day, 17, 0));
+        }
+    }
+
+    #[test]
+    fn window_past_midnight() {
+        let night = "22:00-07:30";
+        assert!(contains(night, 0, 23, 0));
+        assert!(contains(night, 1, 7, 29));
+        assert!(!contains(night, 1, 7, 30));
+        assert!(!contains(night, 1, 12, 0));
+
+        // The early hours belong to the day the window started on
+        let friday_night = "fri 22:00-02:00";
+        assert!(contains(friday_night, 4, 23, 0));
+        assert!(contains(friday_night, 5, 1, 0));
+        assert!(!contains(friday_night, 4, 1, 0));
+        assert!(!contains(friday_night, 6, 1, 0));
+    }
+
+    #[test]
+    fn days_of_the_week() {
+        let weekdays = "mon-fri 09:00-17:00";
+        assert!(contains(weekdays, 0, 10, 0));
+        assert!(contains(weekdays, 4, 10, 0));
+        assert!(!contains(weekdays, 5, 10, 0));
+
+        // Ranges wrap around the week and lists mix with ranges
+        let weekend = "SAT-mon,wed 10:00-12:00";
+        for (day, expected) in [true, false, true, false, false, true, true]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(
+                contains(weekend, day as u32, 11, 0),
+                *expected,
+                "day {}",
+                day
+            );
+        }
+
+        let both = "mon 09:00-10:00; tue 11:00-12:00";
+        assert!(contains(both, 0, 9, 30));
+        assert!(contains(both, 1, 11, 30));
+        assert!(!contains(both, 0, 11, 30));
+    }
+
+    #[test]
+    fn empty_schedule_has_no_windows() {
+        assert!(Schedule::parse("").unwrap().is_empty());
+        assert!(Schedule::parse(" ; ").unwrap().is_empty());
+        assert!(!Schedule::parse("").unwrap().contains(&at(0, 12, 0)));
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        let error = |s| Schedule::parse(s).unwrap_err();
+        assert_eq!(error("25:00-26:00"), "Invalid time \"25:00\", use HH:MM");
+        assert_eq!(error("09:00"), "Missing end time in \"09:00\"");
+        assert!(error("moon 09:00-10:00").starts_with("Unknown day \"moon\""));
+        assert!(error("mon 09:00 - 10:00").starts_with("Expected"));
+        assert!(Schedule::parse("mon 09:00-10:00; nope").is_err());
+    }
+
+    #[test]
+    fn timezones_are_checked() {
+        assert_eq!(parse_timezone("Europe/Athens"), Ok(Tz::Europe__Athens));
+        assert_eq!(
+            parse_timezone("Mars/Olympus"),
+            Err(String::from(
+                "Unknown timezone \"Mars/Olympus\", use a name like Europe/Athens"
+            ))
+        );
+    }
+}
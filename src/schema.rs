@@ -16,3 +16,102 @@ CREATE TABLE IF NOT EXISTS blacklist (
     CONSTRAINT u_black_word UNIQUE ( word )
 );
 "#;
+
+/// Changes applied on top of `SCHEMA`, in order.
+/// The number of migrations already applied is kept in `PRAGMA user_version`,
+/// so only ever append to this list.
+pub static MIGRATIONS: &[&str] = &[
+    // Blacklist scopes and actions
+    r#"
+CREATE TABLE blacklist_new (
+    word TEXT,
+    scope TEXT NOT NULL DEFAULT 'global',
+    scope_id TEXT NOT NULL DEFAULT '',
+    action TEXT NOT NULL DEFAULT 'suppress_all',
+    sound_path TEXT NOT NULL DEFAULT '',
+    CONSTRAINT u_black_word UNIQUE ( word, scope, scope_id )
+);
+INSERT INTO blacklist_new (word) SELECT word FROM blacklist;
+DROP TABLE blacklist;
+ALTER TABLE blacklist_new RENAME TO blacklist;
+"#,
+    // Blacklist match modes
+    r#"
+ALTER TABLE blacklist ADD COLUMN mode TEXT NOT NULL DEFAULT 'substring';
+"#,
+    // Trigger schedules and quiet hours
+    r#"
+ALTER TABLE words ADD COLUMN schedule TEXT NOT NULL DEFAULT '';
+ALTER TABLE settings ADD COLUMN quiet_hours TEXT NOT NULL DEFAULT '';
+ALTER TABLE settings ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+"#,
+    // Trigger probability
+    r#"
+ALTER TABLE words ADD COLUMN probability INTEGER NOT NULL DEFAULT 100;
+"#,
+    // Trigger priority and per channel policies
+    r#"
+ALTER TABLE words ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+CREATE TABLE IF NOT EXISTS channels (
+    chn_id NUMERIC NOT NULL,
+    policy TEXT NOT NULL DEFAULT 'all',
+    CONSTRAINT u_chn UNIQUE ( chn_id )
+);
+"#,
+    // Combo trigger conditions
+    r#"
+ALTER TABLE words ADD COLUMN condition TEXT NOT NULL DEFAULT '';
+"#,
+    // Text to speech triggers
+    r#"
+ALTER TABLE words ADD COLUMN source TEXT NOT NULL DEFAULT 'file';
+ALTER TABLE words ADD COLUMN tts_template TEXT NOT NULL DEFAULT '';
+"#,
+    // Play history
+    r#"
+CREATE TABLE IF NOT EXISTS play_history (
+    played_at INTEGER NOT NULL,
+    guild_id TEXT NOT NULL,
+    chn_id TEXT NOT NULL,
+    author_id TEXT NOT NULL,
+    author TEXT NOT NULL,
+    word TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    outcome TEXT NOT NULL
+);
+ALTER TABLE settings ADD COLUMN history_limit INTEGER NOT NULL DEFAULT 10000;
+"#,
+    // Usage statistics, unlike the history this is never trimmed
+    r#"
+CREATE TABLE IF NOT EXISTS plays (
+    played_at INTEGER NOT NULL,
+    guild_id TEXT NOT NULL,
+    chn_id TEXT NOT NULL,
+    author_id TEXT NOT NULL,
+    author TEXT NOT NULL,
+    word TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS i_plays_played_at ON plays ( played_at );
+"#,
+    // Themes
+    r#"
+ALTER TABLE settings ADD COLUMN theme TEXT NOT NULL DEFAULT 'dark';
+"#,
+    // Dropped files
+    r#"
+ALTER TABLE settings ADD COLUMN default_chn_id TEXT NOT NULL DEFAULT '';
+"#,
+    // Sound library
+    r#"
+ALTER TABLE settings ADD COLUMN library_dir TEXT NOT NULL DEFAULT '';
+ALTER TABLE words ADD COLUMN hash TEXT NOT NULL DEFAULT '';
+"#,
+    // Trigger match modes
+    r#"
+ALTER TABLE words ADD COLUMN mode TEXT NOT NULL DEFAULT 'substring';
+"#,
+    // No token is NULL, older versions saved the input's placeholder instead
+    r#"
+UPDATE settings SET bot_token = NULL WHERE bot_token = 'Bot Token' OR bot_token = '';
+"#,
+];
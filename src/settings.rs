@@ -0,0 +1,53 @@
+use super::style::Theme;
+
+/// Board wide settings, stored next to the token in the `settings` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardSettings {
+    /// Schedule during which nothing is played.
+    pub quiet_hours: String,
+    /// Timezone for all schedules, e.g. `Europe/Athens`.
+    pub timezone: String,
+    /// How many rows of play history to keep.
+    pub history_limit: i32,
+    pub theme: Theme,
+    /// The channel of entries made from dropped files.
+    pub default_chn_id: String,
+    /// Where chosen sound files are copied to, next to the database when empty.
+    pub library_dir: String,
+}
+
+/// Read the history size as typed in the settings.
+pub fn parse_history_limit(value: &str) -> Result<i32, String> {
+    match value.trim().parse() {
+        Ok(limit) if limit >= 1 => Ok(limit),
+        Ok(_) => Err(String::from("Keep at least 1 row")),
+        Err(_) => Err(String::from("Not a number")),
+    }
+}
+
+impl Default for BoardSettings {
+    fn default() -> Self {
+        Self {
+            quiet_hours: String::new(),
+            timezone: String::from("UTC"),
+            history_limit: 10000,
+            theme: Theme::Dark,
+            default_chn_id: String::new(),
+            library_dir: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_limit_is_at_least_one() {
+        assert_eq!(parse_history_limit("500"), Ok(500));
+        assert_eq!(parse_history_limit(" 1 "), Ok(1));
+        assert!(parse_history_limit("0").is_err());
+        assert!(parse_history_limit("-5").is_err());
+        assert!(parse_history_limit("lots").is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Sound packs, zip archives that carry triggers together with their sound files.
+//!
+//! The triggers are in `pack.toml`, in the format of `board_file` without the settings,
+//! and their `path`s point to the files in the `sounds/` folder of the archive.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+
+use super::board_file;
+use super::library;
+use super::word::{Source, Word};
+
+use tokio::task;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const MANIFEST: &str = "pack.toml";
+const SOUNDS: &str = "sounds";
+
+/// Write `words` and the files they play to a pack at `path`.
+/// Returns the words whose files couldn't be read, they are left out.
+pub fn write(path: &str, words: &[Word]) -> Result<Vec<String>, String> {
+    // The pack is written next to `path` and then moved there, so a failure
+    // doesn't leave half a pack behind or destroy the one that was there
+    let part = format!("{}.part", path);
+    let file = File::create(&part).map_err(|e| format!("Failed to create {}: {}", part, e))?;
+    let written = pack(path, file, words).and_then(|skipped| {
+        fs::rename(&part, path)
+            .map(|_| skipped)
+            .map_err(|e| format!("Failed to write {}: {}", path, e))
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&part);
+    }
+    written
+}
+
+/// Write the pack for `path` into `file`.
+fn pack(path: &str, file: File, words: &[Word]) -> Result<Vec<String>, String> {
+    let to_err = |e: zip::result::ZipError| format!("Failed to write {}: {}", path, e);
+    let mut zip = ZipWriter::new(file);
+
+    let mut packed = vec![];
+    let mut skipped = vec![];
+    // Words can share a file, it is only packed once
+    let mut names: HashMap<&str, String> = HashMap::new();
+    for word in words.iter() {
+        if word.source != Source::File {
+            packed.push(word.clone());
+            continue;
+        }
+
+        let name = match names.get(word.path.as_str()) {
+            Some(name) => name.clone(),
+            None => {
+                let data = match fs::read(library::resolve(&word.path)) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        skipped.push(format!("{} ({}: {})", word.word, word.path, e));
+                        continue;
+                    }
+                };
+                let file_name = Path::new(&word.path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| String::from("sound"));
+                // Different files with the same name get a number
+                let mut name = format!("{}/{}", SOUNDS, file_name);
+                let mut n = 1;
+                while names.values().any(|used| *used == name) {
+                    n += 1;
+                    name = format!("{}/{}_{}", SOUNDS, n, file_name);
+                }
+
+                zip.start_file(name.as_str(), FileOptions::default())
+                    .map_err(to_err)?;
+                zip.write_all(&data)
+                    .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+                names.insert(&word.path, name.clone());
+                name
+            }
+        };
+        packed.push(Word {
+            path: name,
+            ..word.clone()
+        });
+    }
+
+    let manifest = board_file::triggers_to_toml(packed)
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    zip.start_file(MANIFEST, FileOptions::default())
+        .map_err(to_err)?;
+    zip.write_all(manifest.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    zip.finish().map_err(to_err)?;
+
+    Ok(skipped)
+}
+
+/// Unpack the pack at `path` into the sound library.
+/// Returns its triggers, playing the unpacked files.
+pub fn read(path: &str) -> Result<Vec<Word>, String> {
+    let to_err = |e: zip::result::ZipError| format!("{} is not a valid sound pack: {}", path, e);
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut zip = ZipArchive::new(file).map_err(to_err)?;
+
+    let mut manifest = String::new();
+    zip.by_name(MANIFEST)
+        .map_err(to_err)?
+        .read_to_string(&mut manifest)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut words = board_file::triggers_from_toml(&manifest)
+        .map_err(|e| format!("{} is not a valid sound pack: {}", path, e))?;
+
+    // Files are unpacked to a temporary folder and `add_file` picks their names
+    // in the library, so they never overwrite a different file with the same name
+    let dir = env::temp_dir().join(format!("sound_board-{}", process::id()));
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let unpacked = unpack(path, &mut zip, &mut words, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    unpacked?;
+
+    Ok(words)
+}
+
+/// Unpack the files of `words` out of the pack at `path` into `dir` one by one
+/// and add them to the library.
+fn unpack(
+    path: &str,
+    zip: &mut ZipArchive<File>,
+    words: &mut [Word],
+    dir: &Path,
+) -> Result<(), String> {
+    for word in words.iter_mut().filter(|w| w.source == Source::File) {
+        // Only the file name is used, so a pack can't write outside of `dir`
+        let file_name = match Path::new(&word.path).file_name() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let target = dir.join(file_name);
+        let mut packed = zip
+            .by_name(&word.path)
+            .map_err(|e| format!("{} is not a valid sound pack: {}", path, e))?;
+        File::create(&target)
+            .and_then(|mut out| io::copy(&mut packed, &mut out))
+            .map_err(|e| format!("Failed to unpack {}: {}", word.path, e))?;
+        let (stored, hash) = library::add_file(&target)?;
+        word.path = stored;
+        word.hash = hash;
+    }
+    Ok(())
+}
+
+/// Ask where to save a pack of `words` and write it there.
+/// Returns the path and the skipped words, or `None` when the user cancelled.
+pub async fn export(words: Vec<Word>) -> Result<Option<(String, Vec<String>)>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_save_dialog(Some("zip"), None).expect("Error opening nfd");
+        let path = match res {
+            nfd::Response::Okay(path) => path,
+            _ => return Ok(None),
+        };
+
+        let skipped = write(&path, &words)?;
+        Ok(Some((path, skipped)))
+    })
+}
+
+/// Ask for a pack and unpack it.
+/// Returns `None` when the user cancelled.
+pub async fn import() -> Result<Option<(String, Vec<Word>)>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_file_dialog(Some("zip"), None).expect("Error opening nfd");
+        let path = match res {
+            nfd::Response::Okay(path) => path,
+            _ => return Ok(None),
+        };
+
+        let words = read(&path)?;
+        Ok(Some((path, words)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("sound_board_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_word(word: &str, path: &Path) -> Word {
+        Word {
+            word: word.to_string(),
+            chn_id: String::from("123456789012345678"),
+            path: path.to_string_lossy().into_owned(),
+            ..Word::default()
+        }
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        let dir = temp_dir("pack");
+        let sources = dir.join("sources");
+        // Two files named `horn.mp3`, and a `2_horn.mp3` that the second one mustn't replace
+        for (path, data) in [
+            ("a/horn.mp3", "first horn"),
+            ("b/horn.mp3", "second horn"),
+            ("c/2_horn.mp3", "not a horn"),
+        ]
+        .iter()
+        {
+            let path = sources.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+
+        let words = vec![
+            file_word("two", &sources.join("c/2_horn.mp3")),
+            file_word("horn", &sources.join("a/horn.mp3")),
+            file_word("again", &sources.join("a/horn.mp3")),
+            file_word("other horn", &sources.join("b/horn.mp3")),
+            file_word("missing", &sources.join("missing.mp3")),
+            Word {
+                source: Source::Tts,
+                tts_template: String::from("hi {author}"),
+                ..file_word("hello", Path::new("Path"))
+            },
+        ];
+        let pack = dir.join("pack.zip");
+        let pack = pack.to_str().unwrap();
+
+        let skipped = write(pack, &words).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("missing"), "{}", skipped[0]);
+        assert!(!Path::new(&format!("{}.part", pack)).exists());
+
+        // Each file is packed once, under its own name
+        let mut zip = ZipArchive::new(File::open(pack).unwrap()).unwrap();
+        let mut names: Vec<String> = (0..zip.len())
+            .map(|i| zip.by_index(i).unwrap().name().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "pack.toml",
+                "sounds/2_horn.mp3",
+                "sounds/3_horn.mp3",
+                "sounds/horn.mp3"
+            ]
+        );
+
+        library::set_root(dir.join("library").to_str().unwrap());
+        let unpacked = read(pack).unwrap();
+        let expected: Vec<&Word> = words.iter().filter(|w| w.word != "missing").collect();
+        assert_eq!(unpacked.len(), expected.len());
+        for (read, word) in unpacked.iter().zip(expected) {
+            if word.source == Source::File {
+                assert_eq!(
+                    fs::read(library::resolve(&read.path)).unwrap(),
+                    fs::read(&word.path).unwrap()
+                );
+                assert_eq!(
+                    read.hash,
+                    library::hash_file(Path::new(&word.path)).unwrap()
+                );
+            }
+            let read = Word {
+                path: word.path.clone(),
+                hash: word.hash.clone(),
+                ..read.clone()
+            };
+            assert_eq!(read, *word);
+        }
+        // Words that shared a file still do
+        assert_eq!(unpacked[1].path, unpacked[2].path);
+        assert_ne!(unpacked[1].path, unpacked[3].path);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_write_leaves_nothing_behind() {
+        let dir = temp_dir("pack_fail");
+        // A pack can't replace a folder
+        let taken = dir.join("taken.zip");
+        fs::create_dir(&taken).unwrap();
+        let path = taken.to_str().unwrap();
+
+        assert!(write(path, &[]).is_err());
+        assert!(taken.is_dir());
+        assert!(!Path::new(&format!("{}.part", path)).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
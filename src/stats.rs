@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Timelike};
+use chrono_tz::Tz;
+use tokio::task;
+
+/// How far back the statistics go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Range {
+    Day,
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl Default for Range {
+    fn default() -> Self {
+        Range::Week
+    }
+}
+
+impl Range {
+    pub fn next(self) -> Self {
+        match self {
+            Range::Day => Range::Week,
+            Range::Week => Range::Month,
+            Range::Month => Range::Year,
+            Range::Year => Range::All,
+            Range::All => Range::Day,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Range::Day => "Last 24 hours",
+            Range::Week => "Last 7 days",
+            Range::Month => "Last 30 days",
+            Range::Year => "Last year",
+            Range::All => "All time",
+        }
+    }
+
+    /// The earliest unix timestamp in the range, when it ends at `now`.
+    pub fn since(self, now: i64) -> i64 {
+        let days = match self {
+            Range::Day => 1,
+            Range::Week => 7,
+            Range::Month => 30,
+            Range::Year => 365,
+            Range::All => return 0,
+        };
+        now - days * 24 * 60 * 60
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Count {
+    pub name: String,
+    pub plays: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub triggers: Vec<Count>,
+    pub users: Vec<Count>,
+    pub channels: Vec<Count>,
+    /// Every hour of the day, in the board's timezone.
+    pub hours: Vec<Count>,
+    /// Every day with at least one play, oldest first.
+    pub days: Vec<Count>,
+}
+
+impl Stats {
+    /// Fill `hours` and `days` from the plays counted per `BUCKET` seconds.
+    pub fn set_times(&mut self, buckets: &[(i64, i64)], tz: Tz) {
+        let mut hours = [0; 24];
+        let mut days = BTreeMap::new();
+
+        for (start, plays) in buckets {
+            let time = tz.timestamp_opt(*start, 0).unwrap();
+            hours[time.hour() as usize] += plays;
+            *days.entry(time.format("%Y-%m-%d").to_string()).or_insert(0) += plays;
+        }
+
+        self.hours = hours
+            .iter()
+            .enumerate()
+            .map(|(hour, plays)| Count {
+                name: format!("{:02}:00", hour),
+                plays: *plays,
+            })
+            .collect();
+        self.days = days
+            .into_iter()
+            .map(|(name, plays)| Count { name, plays })
+            .collect();
+    }
+
+    fn sections(&self) -> [(&'static str, &[Count]); 5] {
+        [
+            ("trigger", &self.triggers),
+            ("user", &self.users),
+            ("channel", &self.channels),
+            ("hour", &self.hours),
+            ("day", &self.days),
+        ]
+    }
+
+    fn write_csv(&self, path: &str) -> csv::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["section", "name", "plays"])?;
+        for (section, counts) in self.sections().iter() {
+            for count in counts.iter() {
+                writer.write_record([section, count.name.as_str(), &count.plays.to_string()])?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Plays are counted in buckets of this many seconds, so that they can be
+/// moved to any timezone, they all have offsets in multiples of 15 minutes.
+pub const BUCKET: i64 = 15 * 60;
+
+/// Ask where to save `stats` and write them there as CSV.
+/// Returns the path, or `None` when the user cancelled.
+pub async fn export(stats: Stats) -> Result<Option<String>, String> {
+    task::block_in_place(|| {
+        let res = nfd::open_save_dialog(Some("csv"), None).expect("Error opening nfd");
+        let path = match res {
+            nfd::Response::Okay(path) => path,
+            _ => return Ok(None),
+        };
+
+        stats
+            .write_csv(&path)
+            .map_err(|e| format!("Failed to export statistics: {}", e))?;
+        Ok(Some(path))
+    })
+}
@@ -1,12 +1,62 @@
-use iced::{button, container, scrollable, text_input};
+use iced::{button, container, scrollable, text_input, Color};
+
+/// Color for validation errors next to the inputs.
+pub const ERROR_COLOR: Color = Color::from_rgb(
+    0xF0 as f32 / 255.0,
+    0x47 as f32 / 255.0,
+    0x47 as f32 / 255.0,
+);
+
+/// Color for entries whose sound file can't be played.
+pub const WARNING_COLOR: Color = Color::from_rgb(
+    0xF0 as f32 / 255.0,
+    0xA5 as f32 / 255.0,
+    0x2A as f32 / 255.0,
+);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Theme {
     Dark,
+    Light,
+    HighContrast,
 }
 
 impl Theme {
-    pub const ALL: [Theme; 1] = [Theme::Dark];
+    pub const ALL: [Theme; 3] = [Theme::Dark, Theme::Light, Theme::HighContrast];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+            Theme::HighContrast => "high_contrast",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::HighContrast,
+            Theme::HighContrast => Theme::Dark,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::HighContrast => "High contrast",
+        }
+    }
+}
+
+impl From<&str> for Theme {
+    fn from(s: &str) -> Self {
+        match s {
+            "light" => Theme::Light,
+            "high_contrast" => Theme::HighContrast,
+            _ => Theme::Dark,
+        }
+    }
 }
 
 impl Default for Theme {
@@ -16,26 +66,42 @@ impl Default for Theme {
 }
 
 impl From<Theme> for Box<dyn container::StyleSheet> {
-    fn from(_: Theme) -> Self {
-        dark::Container.into()
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => dark::Container.into(),
+            Theme::Light => light::Container.into(),
+            Theme::HighContrast => high_contrast::Container.into(),
+        }
     }
 }
 
 impl From<Theme> for Box<dyn text_input::StyleSheet> {
-    fn from(_: Theme) -> Self {
-        dark::TextInput.into()
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => dark::TextInput.into(),
+            Theme::Light => light::TextInput.into(),
+            Theme::HighContrast => high_contrast::TextInput.into(),
+        }
     }
 }
 
 impl From<Theme> for Box<dyn button::StyleSheet> {
-    fn from(_: Theme) -> Self {
-        dark::Button.into()
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => dark::Button.into(),
+            Theme::Light => light::Button.into(),
+            Theme::HighContrast => high_contrast::Button.into(),
+        }
     }
 }
 
 impl From<Theme> for Box<dyn scrollable::StyleSheet> {
-    fn from(_: Theme) -> Self {
-        dark::Scrollable.into()
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => dark::Scrollable.into(),
+            Theme::Light => light::Scrollable.into(),
+            Theme::HighContrast => high_contrast::Scrollable.into(),
+        }
     }
 }
 
@@ -192,3 +258,295 @@ mod dark {
         }
     }
 }
+
+mod light {
+    use iced::{button, container, scrollable, text_input, Background, Color};
+
+    const SURFACE: Color = Color::from_rgb(
+        0xE3 as f32 / 255.0,
+        0xE5 as f32 / 255.0,
+        0xE8 as f32 / 255.0,
+    );
+
+    const ACCENT: Color = Color::from_rgb(
+        0x00 as f32 / 255.0,
+        0x96 as f32 / 255.0,
+        0x88 as f32 / 255.0,
+    );
+
+    const ACTIVE: Color = Color::from_rgb(
+        0x58 as f32 / 255.0,
+        0x65 as f32 / 255.0,
+        0xF2 as f32 / 255.0,
+    );
+
+    const HOVERED: Color = Color::from_rgb(
+        0x47 as f32 / 255.0,
+        0x52 as f32 / 255.0,
+        0xC4 as f32 / 255.0,
+    );
+
+    const TEXT: Color = Color::from_rgb(
+        0x2E as f32 / 255.0,
+        0x33 as f32 / 255.0,
+        0x38 as f32 / 255.0,
+    );
+
+    pub struct Container;
+
+    impl container::StyleSheet for Container {
+        fn style(&self) -> container::Style {
+            container::Style {
+                background: Some(Background::Color(Color::WHITE)),
+                text_color: Some(TEXT),
+                ..container::Style::default()
+            }
+        }
+    }
+
+    pub struct TextInput;
+
+    impl text_input::StyleSheet for TextInput {
+        fn active(&self) -> text_input::Style {
+            text_input::Style {
+                background: Background::Color(SURFACE),
+                border_radius: 2,
+                border_width: 0,
+                border_color: Color::TRANSPARENT,
+            }
+        }
+
+        fn focused(&self) -> text_input::Style {
+            text_input::Style {
+                border_width: 1,
+                border_color: ACCENT,
+                ..self.active()
+            }
+        }
+
+        fn placeholder_color(&self) -> Color {
+            Color::from_rgb(0.55, 0.55, 0.55)
+        }
+
+        fn value_color(&self) -> Color {
+            TEXT
+        }
+
+        fn selection_color(&self) -> Color {
+            Color { a: 0.4, ..ACTIVE }
+        }
+
+        fn hovered(&self) -> text_input::Style {
+            text_input::Style {
+                border_width: 1,
+                border_color: Color { a: 0.3, ..ACCENT },
+                ..self.focused()
+            }
+        }
+    }
+
+    pub struct Button;
+
+    impl button::StyleSheet for Button {
+        fn active(&self) -> button::Style {
+            button::Style {
+                background: Some(Background::Color(ACTIVE)),
+                border_radius: 3,
+                text_color: Color::WHITE,
+                ..button::Style::default()
+            }
+        }
+
+        fn hovered(&self) -> button::Style {
+            button::Style {
+                background: Some(Background::Color(HOVERED)),
+                text_color: Color::WHITE,
+                ..self.active()
+            }
+        }
+
+        fn pressed(&self) -> button::Style {
+            button::Style {
+                border_width: 1,
+                border_color: TEXT,
+                ..self.hovered()
+            }
+        }
+    }
+
+    pub struct Scrollable;
+
+    impl scrollable::StyleSheet for Scrollable {
+        fn active(&self) -> scrollable::Scrollbar {
+            scrollable::Scrollbar {
+                background: Some(Background::Color(SURFACE)),
+                border_radius: 2,
+                border_width: 0,
+                border_color: Color::TRANSPARENT,
+                scroller: scrollable::Scroller {
+                    color: ACTIVE,
+                    border_radius: 2,
+                    border_width: 0,
+                    border_color: Color::TRANSPARENT,
+                },
+            }
+        }
+
+        fn hovered(&self) -> scrollable::Scrollbar {
+            let active = self.active();
+
+            scrollable::Scrollbar {
+                background: Some(Background::Color(Color { a: 0.5, ..SURFACE })),
+                scroller: scrollable::Scroller {
+                    color: HOVERED,
+                    ..active.scroller
+                },
+                ..active
+            }
+        }
+
+        fn dragging(&self) -> scrollable::Scrollbar {
+            let hovered = self.hovered();
+
+            scrollable::Scrollbar {
+                scroller: scrollable::Scroller {
+                    color: TEXT,
+                    ..hovered.scroller
+                },
+                ..hovered
+            }
+        }
+    }
+}
+
+/// Black and white with yellow highlights, with borders on everything that can be used.
+mod high_contrast {
+    use iced::{button, container, scrollable, text_input, Background, Color};
+
+    const HIGHLIGHT: Color = Color::from_rgb(
+        0xFF as f32 / 255.0,
+        0xD7 as f32 / 255.0,
+        0x00 as f32 / 255.0,
+    );
+
+    pub struct Container;
+
+    impl container::StyleSheet for Container {
+        fn style(&self) -> container::Style {
+            container::Style {
+                background: Some(Background::Color(Color::BLACK)),
+                text_color: Some(Color::WHITE),
+                ..container::Style::default()
+            }
+        }
+    }
+
+    pub struct TextInput;
+
+    impl text_input::StyleSheet for TextInput {
+        fn active(&self) -> text_input::Style {
+            text_input::Style {
+                background: Background::Color(Color::BLACK),
+                border_radius: 0,
+                border_width: 2,
+                border_color: Color::WHITE,
+            }
+        }
+
+        fn focused(&self) -> text_input::Style {
+            text_input::Style {
+                border_width: 3,
+                border_color: HIGHLIGHT,
+                ..self.active()
+            }
+        }
+
+        fn placeholder_color(&self) -> Color {
+            Color::from_rgb(0.75, 0.75, 0.75)
+        }
+
+        fn value_color(&self) -> Color {
+            Color::WHITE
+        }
+
+        fn selection_color(&self) -> Color {
+            Color {
+                a: 0.5,
+                ..HIGHLIGHT
+            }
+        }
+
+        fn hovered(&self) -> text_input::Style {
+            text_input::Style {
+                border_color: HIGHLIGHT,
+                ..self.active()
+            }
+        }
+    }
+
+    pub struct Button;
+
+    impl button::StyleSheet for Button {
+        fn active(&self) -> button::Style {
+            button::Style {
+                background: Some(Background::Color(Color::BLACK)),
+                border_radius: 0,
+                border_width: 2,
+                border_color: Color::WHITE,
+                text_color: Color::WHITE,
+                ..button::Style::default()
+            }
+        }
+
+        fn hovered(&self) -> button::Style {
+            button::Style {
+                border_color: HIGHLIGHT,
+                text_color: HIGHLIGHT,
+                ..self.active()
+            }
+        }
+
+        fn pressed(&self) -> button::Style {
+            button::Style {
+                background: Some(Background::Color(HIGHLIGHT)),
+                text_color: Color::BLACK,
+                ..self.hovered()
+            }
+        }
+    }
+
+    pub struct Scrollable;
+
+    impl scrollable::StyleSheet for Scrollable {
+        fn active(&self) -> scrollable::Scrollbar {
+            scrollable::Scrollbar {
+                background: Some(Background::Color(Color::BLACK)),
+                border_radius: 0,
+                border_width: 1,
+                border_color: Color::WHITE,
+                scroller: scrollable::Scroller {
+                    color: Color::WHITE,
+                    border_radius: 0,
+                    border_width: 0,
+                    border_color: Color::TRANSPARENT,
+                },
+            }
+        }
+
+        fn hovered(&self) -> scrollable::Scrollbar {
+            let active = self.active();
+
+            scrollable::Scrollbar {
+                scroller: scrollable::Scroller {
+                    color: HIGHLIGHT,
+                    ..active.scroller
+                },
+                ..active
+            }
+        }
+
+        fn dragging(&self) -> scrollable::Scrollbar {
+            self.hovered()
+        }
+    }
+}
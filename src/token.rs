@@ -0,0 +1,148 @@
+//! Where the bot token comes from. `SOUND_BOARD_TOKEN` and the file named by
+//! `SOUND_BOARD_TOKEN_FILE` keep it out of the database. Otherwise it is stored there,
+//! encrypted with ChaCha20-Poly1305 and the key in `token.key` in the data directory.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::db;
+use super::profile;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{rngs::OsRng, RngCore};
+
+pub const TOKEN_VAR: &str = "SOUND_BOARD_TOKEN";
+pub const TOKEN_FILE_VAR: &str = "SOUND_BOARD_TOKEN_FILE";
+
+/// Marks encrypted tokens, older ones were stored as they are.
+const PREFIX: &str = "enc1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Nothing was entered yet.
+    None,
+    Env,
+    /// The file at this path.
+    File(String),
+    /// Encrypted in the database.
+    Database,
+    /// In the database from before it was encrypted.
+    Plaintext,
+}
+
+impl Source {
+    /// Determine if the token is kept out of the database.
+    pub fn is_external(&self) -> bool {
+        matches!(self, Source::Env | Source::File(_))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Source::None => String::from("Not set, it is encrypted when saved"),
+            Source::Env => format!("From the {} variable", TOKEN_VAR),
+            Source::File(path) => format!("From {}", path),
+            Source::Database => String::from("Encrypted in the database"),
+            Source::Plaintext => String::from("Not encrypted yet, save to encrypt it"),
+        }
+    }
+}
+
+/// Read the token from where it is.
+pub async fn load() -> Result<(String, Source), String> {
+    if let Ok(token) = env::var(TOKEN_VAR) {
+        return Ok((token.trim().to_string(), Source::Env));
+    }
+    if let Ok(path) = env::var(TOKEN_FILE_VAR) {
+        let token =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        return Ok((token.trim().to_string(), Source::File(path)));
+    }
+
+    match db::get_token().await {
+        None => Ok((String::new(), Source::None)),
+        Some(stored) if stored.starts_with(PREFIX) => Ok((decrypt(&stored)?, Source::Database)),
+        Some(stored) => Ok((stored, Source::Plaintext)),
+    }
+}
+
+/// What to store in the database for `token`.
+pub fn encrypt(token: &str) -> Result<String, String> {
+    if token.is_empty() {
+        return Ok(String::new());
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key()?));
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let encrypted = cipher
+        .encrypt(Nonce::from_slice(&nonce), token.as_bytes())
+        .map_err(|_| String::from("Failed to encrypt the token"))?;
+
+    let mut data = nonce.to_vec();
+    data.extend(encrypted);
+    Ok(format!("{}{}", PREFIX, base64::encode(data)))
+}
+
+fn decrypt(stored: &str) -> Result<String, String> {
+    let data = base64::decode(stored.trim_start_matches(PREFIX))
+        .map_err(|_| String::from("The stored token is damaged, enter it again"))?;
+    if data.len() < NONCE_LEN {
+        return Err(String::from("The stored token is damaged, enter it again"));
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key()?));
+    let (nonce, encrypted) = data.split_at(NONCE_LEN);
+    let token = cipher
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| {
+            format!(
+                "Failed to decrypt the token, {} isn't the key it was saved with",
+                key_path().display()
+            )
+        })?;
+    String::from_utf8(token).map_err(|_| String::from("The stored token is damaged"))
+}
+
+fn key_path() -> PathBuf {
+    profile::data_dir().join("token.key")
+}
+
+/// The key in `key_path`, made the first time it is needed.
+fn key() -> Result<[u8; KEY_LEN], String> {
+    let path = key_path();
+    let mut key = [0; KEY_LEN];
+    match fs::read(&path) {
+        Ok(data) if data.len() == KEY_LEN => {
+            key.copy_from_slice(&data);
+            return Ok(key);
+        }
+        Ok(_) => return Err(format!("{} isn't a valid key", path.display())),
+        // A key that can't be read mustn't be replaced, the stored token needs it
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(format!("Failed to read {}: {}", path.display(), e))
+        }
+        Err(_) => {}
+    }
+
+    OsRng.fill_bytes(&mut key);
+    let dir = profile::data_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Only the user should be able to read it, from the moment it exists
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(&key))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(key)
+}
@@ -0,0 +1,153 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The offline engine used for text to speech, it has to be in `PATH`.
+pub const ENGINE: &str = "espeak-ng";
+
+/// Replace the `{name}` placeholders of `template` with their values.
+/// Values are never searched for placeholders, so a message can't expand into others.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let value = rest.find('}').and_then(|close| {
+            let name = &rest[1..close];
+            let value = vars.iter().find(|(n, _)| *n == name)?.1;
+            Some((value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                text.push_str(value);
+                rest = &rest[close + 1..];
+            }
+            // Not a placeholder, the brace is kept as it is
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// How long the engine gets to speak a message before it is killed.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Speak `text` into a wav file, returned as bytes.
+/// This blocks until the engine is done, or killed after `timeout`.
+pub fn synthesize(text: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    // The text goes through stdin so it can't be mistaken for arguments
+    let mut command = Command::new(ENGINE);
+    command.args(["--stdin", "--stdout"]);
+    run(command, text, timeout)
+}
+
+/// Run `command` with `input` as its stdin, killing it if it runs longer than `timeout`.
+fn run(mut command: Command, input: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", ENGINE, e))?;
+
+    // Every pipe gets its own thread, so a stuck engine can't block us past the timeout
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let stdout = read_all(child.stdout.take().unwrap());
+    let stderr = read_all(child.stderr.take().unwrap());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} took longer than {} seconds and was stopped",
+                    ENGINE,
+                    timeout.as_secs_f32()
+                ));
+            }
+            Err(e) => return Err(format!("Failed to run {}: {}", ENGINE, e)),
+        }
+    };
+
+    writer
+        .join()
+        .unwrap()
+        .map_err(|e| format!("Failed to send text to {}: {}", ENGINE, e))?;
+    if !status.success() {
+        let stderr = stderr.join().unwrap();
+        return Err(format!(
+            "{} failed: {}",
+            ENGINE,
+            String::from_utf8_lossy(&stderr).trim()
+        ));
+    }
+
+    Ok(stdout.join().unwrap())
+}
+
+fn read_all<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = vec![];
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: &[(&str, &str)] = &[("author", "{message}"), ("message", "say {word}")];
+
+    #[test]
+    fn fills_placeholders() {
+        assert_eq!(
+            render(
+                "{author} said {message}!",
+                &[("author", "ann"), ("message", "hi")]
+            ),
+            "ann said hi!"
+        );
+    }
+
+    #[test]
+    fn values_are_not_expanded() {
+        assert_eq!(render("{author}: {message}", VARS), "{message}: say {word}");
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_braces() {
+        assert_eq!(render("{other} {message", VARS), "{other} {message");
+        assert_eq!(render("{{message}}", VARS), "{say {word}}");
+    }
+
+    #[test]
+    fn returns_the_output() {
+        let output = run(Command::new("cat"), "hello", Duration::from_secs(5));
+        assert_eq!(output, Ok(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn kills_after_the_timeout() {
+        let mut command = Command::new("sleep");
+        command.arg("5");
+
+        let started = Instant::now();
+        let output = run(command, "", Duration::from_millis(100));
+        assert!(output.unwrap_err().contains("was stopped"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,143 @@
+use super::blacklist::BlackWord;
+use super::policy::ChannelPolicy;
+use super::word::Word;
+
+/// How many steps can be undone.
+const MAX_STEPS: usize = 100;
+
+/// The entries of the board at one point, keyed by their ids.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    pub words: Vec<(i64, Word)>,
+    pub blacklist: Vec<(i64, BlackWord)>,
+    pub policies: Vec<(i64, ChannelPolicy)>,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History {
+    /// Remember `before`, the state before a change.
+    pub fn record(&mut self, before: Snapshot) {
+        self.push(before);
+        self.redo.clear();
+    }
+
+    /// Remember `before`, the state before an edit that may change nothing.
+    /// What can be redone is kept until `drop_if_unchanged` finds that it did.
+    pub fn begin(&mut self, before: Snapshot) {
+        self.push(before);
+    }
+
+    /// Forget the last step when nothing changed since it was recorded,
+    /// otherwise the change is kept and nothing can be redone anymore.
+    pub fn drop_if_unchanged(&mut self, current: &Snapshot) {
+        if self.undo.last() == Some(current) {
+            self.undo.pop();
+        } else {
+            self.redo.clear();
+        }
+    }
+
+    fn push(&mut self, before: Snapshot) {
+        self.undo.push(before);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    /// The state to go back to, `current` can then be redone.
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let before = self.undo.pop()?;
+        self.redo.push(current);
+        Some(before)
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let after = self.redo.pop()?;
+        self.undo.push(current);
+        Some(after)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_words(words: &[&str]) -> Snapshot {
+        Snapshot {
+            words: words
+                .iter()
+                .enumerate()
+                .map(|(id, word)| {
+                    let word = Word {
+                        word: word.to_string(),
+                        ..Word::default()
+                    };
+                    (id as i64, word)
+                })
+                .collect(),
+            ..Snapshot::default()
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        history.record(with_words(&[]));
+
+        let current = with_words(&["a"]);
+        assert_eq!(history.undo(current.clone()), Some(with_words(&[])));
+        assert!(!history.can_undo());
+        assert_eq!(history.redo(with_words(&[])), Some(current));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn unchanged_edit_keeps_redo() {
+        let mut history = History::default();
+        history.record(with_words(&[]));
+        history.undo(with_words(&["a"]));
+
+        history.begin(with_words(&[]));
+        history.drop_if_unchanged(&with_words(&[]));
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn changed_edit_clears_redo() {
+        let mut history = History::default();
+        history.record(with_words(&[]));
+        history.undo(with_words(&["a"]));
+
+        history.begin(with_words(&[]));
+        history.drop_if_unchanged(&with_words(&["b"]));
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(with_words(&["b"])), Some(with_words(&[])));
+    }
+
+    #[test]
+    fn steps_are_limited() {
+        let mut history = History::default();
+        for i in 0..MAX_STEPS + 10 {
+            history.record(with_words(&[&i.to_string()]));
+        }
+        let mut steps = 0;
+        while history.undo(Snapshot::default()).is_some() {
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_STEPS);
+    }
+}
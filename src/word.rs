@@ -1,31 +1,356 @@
-#[derive(Debug, Clone)]
+use super::condition::{self, Context, Expr};
+use super::match_mode::{MatchMode, Pattern, Span};
+use super::schedule::Schedule;
+
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{DateTime, TimeZone};
+use rand::Rng;
+
+/// What is played when a word matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The sound file at `path`.
+    File,
+    /// `tts_template` read out loud.
+    Tts,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::File => "file",
+            Source::Tts => "tts",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Source::File => Source::Tts,
+            Source::Tts => Source::File,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::File => "Sound file",
+            Source::Tts => "Text to speech",
+        }
+    }
+}
+
+impl From<&str> for Source {
+    fn from(s: &str) -> Self {
+        match s {
+            "tts" => Source::Tts,
+            _ => Source::File,
+        }
+    }
+}
+
+/// The fields of a word that can be invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Word,
+    ChnId,
+    Sound,
+    Schedule,
+    Probability,
+    Priority,
+    Condition,
+}
+
+/// Discord ids below this would be from before Discord existed.
+const MIN_SNOWFLAKE: u64 = 1 << 22;
+
+/// Determine if `id` looks like a Discord id.
+pub fn is_snowflake(id: &str) -> bool {
+    match id.parse::<u64>() {
+        Ok(id) => id >= MIN_SNOWFLAKE,
+        Err(_) => false,
+    }
+}
+
+/// A keyword made from a file name, e.g. `Air_Horn.mp3` becomes `air horn`.
+pub fn keyword_from_file(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().replace(['_', '-'], " "))
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Word {
     pub word: String,
+    /// How `word` is looked for in messages, unless there is a condition.
+    pub mode: MatchMode,
     pub chn_id: String,
+    /// Relative to the sound library, unless the file is outside of it. See `library`.
     pub path: String,
+    /// Hash of the file at `path`, to find it again when it is moved. Empty when unknown.
+    pub hash: String,
+    /// When the word is active, empty means always. See `Schedule`.
+    pub schedule: String,
+    /// Chance of playing when matched, from 0 to 100.
+    pub probability: i32,
+    /// Used by `Policy::HighestPriority`, higher wins.
+    pub priority: i32,
+    /// Used instead of `word` to match when it isn't empty. See `condition`.
+    pub condition: String,
+    pub source: Source,
+    /// What to say with `Source::Tts`, can use `{author}`, `{message}` and `{word}`.
+    pub tts_template: String,
 }
 
 impl Default for Word {
     fn default() -> Self {
         Self {
             word: String::new(),
+            mode: MatchMode::Substring,
             chn_id: String::new(),
             path: String::from("Path"),
+            hash: String::new(),
+            schedule: String::new(),
+            probability: 100,
+            priority: 0,
+            condition: String::new(),
+            source: Source::File,
+            tts_template: String::from("{message}"),
         }
     }
 }
 
 impl Word {
-    fn is_empty(&self) -> bool {
-        self.word.is_empty() || self.path.is_empty() || self.chn_id.is_empty()
+    /// What is wrong with each invalid field.
+    /// The sound file isn't opened here, that is slow, see `player::check`.
+    pub fn errors(&self) -> Vec<(Field, String)> {
+        let mut errors = vec![];
+
+        if self.word.trim().is_empty() {
+            errors.push((Field::Word, "The word can't be empty".to_string()));
+        } else if let (true, Err(e)) = (self.condition.is_empty(), self.mode.validate(&self.word)) {
+            errors.push((Field::Word, e));
+        }
+        if self.chn_id.is_empty() {
+            errors.push((Field::ChnId, "The channel id can't be empty".to_string()));
+        } else if !is_snowflake(&self.chn_id) {
+            errors.push((Field::ChnId, "Not a Discord channel id".to_string()));
+        }
+        if self.source == Source::Tts && self.tts_template.trim().is_empty() {
+            errors.push((Field::Sound, "There is nothing to say".to_string()));
+        }
+        if let Err(e) = Schedule::parse(&self.schedule) {
+            errors.push((Field::Schedule, e));
+        }
+        if !(0..=100).contains(&self.probability) {
+            errors.push((Field::Probability, "From 0 to 100".to_string()));
+        }
+        if let (false, Err(e)) = (self.condition.is_empty(), condition::parse(&self.condition)) {
+            errors.push((Field::Condition, e.to_string()));
+        }
+
+        errors
+    }
+
+    /// Parse the pattern, condition and schedule for matching.
+    pub fn matcher(&self) -> Matcher {
+        let rule = if self.condition.is_empty() {
+            Rule::Pattern(Pattern::new(self.mode, &self.word))
+        } else {
+            match condition::parse(&self.condition) {
+                Ok(expr) => Rule::Condition(expr),
+                Err(_) => Rule::Invalid,
+            }
+        };
+
+        Matcher {
+            rule,
+            schedule: Schedule::parse(&self.schedule).ok(),
+        }
+    }
+
+    /// Decide if a match should play, according to the word's probability.
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> bool {
+        rng.gen_range(0, 100) < self.probability
     }
+}
+
+/// Pair each word with its matcher.
+pub fn with_matchers(words: Vec<Word>) -> Vec<(Word, Matcher)> {
+    words
+        .into_iter()
+        .map(|word| {
+            let matcher = word.matcher();
+            (word, matcher)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Pattern(Pattern),
+    Condition(Expr),
+    /// The condition doesn't parse, it never matches.
+    Invalid,
+}
+
+/// A word ready to be looked for, it is parsed once when it is made
+/// rather than for every message. See `Word::matcher`.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    rule: Rule,
+    /// `None` when the schedule is invalid, the word is then never active.
+    schedule: Option<Schedule>,
+}
+
+impl Matcher {
+    /// Find where the word matches the lowercase `text`,
+    /// `recent` are the author's earlier messages for combo conditions.
+    /// A condition that matched with none of its words in `text`, like `!bye`,
+    /// gets the empty span at the start.
+    pub fn matches(&self, text: &str, recent: &[(Instant, String)]) -> Option<Span> {
+        let expr = match &self.rule {
+            Rule::Pattern(pattern) => return pattern.find(text),
+            Rule::Condition(expr) => expr,
+            Rule::Invalid => return None,
+        };
 
-    /// Determine if the channel id related to the word is numeric
-    fn id_numeric(&self) -> bool {
-        self.chn_id.chars().all(char::is_numeric)
+        let ctx = Context {
+            text,
+            recent,
+            now: Instant::now(),
+        };
+        if !expr.eval(&ctx) {
+            return None;
+        }
+        Some(expr.span(&ctx).unwrap_or((0, 0)))
+    }
+
+    /// Determine if the word is active at `now`, according to its schedule.
+    pub fn is_active<T: TimeZone>(&self, now: &DateTime<T>) -> bool {
+        match &self.schedule {
+            Some(schedule) => schedule.is_empty() || schedule.contains(now),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{NaiveDate, Utc};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn with_probability(probability: i32) -> Word {
+        Word {
+            probability,
+            ..Word::default()
+        }
     }
 
-    pub fn is_valid(&self) -> bool {
-        !self.is_empty() && self.id_numeric()
+    fn rolls(word: &Word, seed: u64) -> Vec<bool> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..1000).map(|_| word.roll(&mut rng)).collect()
+    }
+
+    #[test]
+    fn never_plays_at_zero() {
+        assert!(rolls(&with_probability(0), 1).iter().all(|played| !played));
+    }
+
+    #[test]
+    fn always_plays_at_hundred() {
+        assert!(rolls(&with_probability(100), 1)
+            .iter()
+            .all(|played| *played));
+    }
+
+    #[test]
+    fn same_seed_same_rolls() {
+        let word = with_probability(50);
+        let first = rolls(&word, 42);
+        assert_eq!(first, rolls(&word, 42));
+        assert_ne!(first, rolls(&word, 43));
+
+        let played = first.iter().filter(|played| **played).count();
+        assert!((400..600).contains(&played), "played {} of 1000", played);
+    }
+
+    fn trigger(word: &str, mode: MatchMode) -> Word {
+        Word {
+            word: word.to_string(),
+            mode,
+            ..Word::default()
+        }
+    }
+
+    #[test]
+    fn matches_with_the_mode() {
+        let ass = |mode| trigger("ass", mode);
+        assert_eq!(
+            ass(MatchMode::Substring)
+                .matcher()
+                .matches("first class", &[]),
+            Some((8, 11))
+        );
+        assert_eq!(
+            ass(MatchMode::WholeWord)
+                .matcher()
+                .matches("first class", &[]),
+            None
+        );
+        assert_eq!(
+            ass(MatchMode::WholeWord)
+                .matcher()
+                .matches("class, ass", &[]),
+            Some((7, 10))
+        );
+
+        let horn = trigger(r"ho+rn", MatchMode::Regex);
+        assert_eq!(horn.matcher().matches("a hoooorn", &[]), Some((2, 9)));
+    }
+
+    #[test]
+    fn condition_matches_where_its_words_are() {
+        let word = Word {
+            condition: String::from("hi & !bye"),
+            ..trigger("greeting", MatchMode::WholeWord)
+        };
+        let matcher = word.matcher();
+        assert_eq!(matcher.matches("oh hi", &[]), Some((3, 5)));
+        assert_eq!(matcher.matches("hi, bye", &[]), None);
+
+        let word = Word {
+            condition: String::from("!bye"),
+            ..word
+        };
+        assert_eq!(word.matcher().matches("oh hi", &[]), Some((0, 0)));
+
+        let word = Word {
+            condition: String::from("(hi"),
+            ..word
+        };
+        assert_eq!(word.matcher().matches("oh hi", &[]), None);
+    }
+
+    #[test]
+    fn schedule_decides_when_it_is_active() {
+        let monday = NaiveDate::from_ymd_opt(2021, 3, 1).unwrap();
+        let at = |h| Utc.from_utc_datetime(&monday.and_hms_opt(h, 0, 0).unwrap());
+        let word = |schedule: &str| Word {
+            schedule: schedule.to_string(),
+            ..Word::default()
+        };
+
+        assert!(word("").matcher().is_active(&at(3)));
+        let office = word("mon-fri 09:00-17:00").matcher();
+        assert!(office.is_active(&at(10)));
+        assert!(!office.is_active(&at(18)));
+        assert!(!word("mon 25:00-26:00").matcher().is_active(&at(10)));
     }
 }